@@ -1,8 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::{Direction, Vector2D};
+use crate::util::{Direction, Grid, ParseError, Polygon, Vector2D};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Pipe {
@@ -14,31 +14,27 @@ enum Pipe {
     F,
 }
 
-type Pipes = HashMap<Vector2D, Pipe>;
+type Pipes = Grid<Option<Pipe>>;
 
 #[derive(Debug, Clone)]
 struct Input {
-    width: i32,
-    height: i32,
     pipes: Pipes,
     start: Vector2D,
 }
 
-impl From<char> for Pipe {
-    fn from(value: char) -> Self {
-        match value {
+impl Pipe {
+    fn parse(c: char) -> Option<Pipe> {
+        Some(match c {
             '|' => Pipe::Vert,
             '-' => Pipe::Hori,
             'L' => Pipe::L,
             'J' => Pipe::J,
             '7' => Pipe::Seven,
             'F' => Pipe::F,
-            c => panic!("invalid pipe {c}"),
-        }
+            _ => return None,
+        })
     }
-}
 
-impl Pipe {
     fn neighbours(self) -> [Direction; 2] {
         match self {
             Pipe::Vert => [Direction::N, Direction::S],
@@ -87,30 +83,23 @@ impl Pipe {
 }
 
 #[aoc_generator(day10)]
-fn parse(input: &str) -> Input {
-    let height = input.lines().count() as i32;
-    let width = input.lines().next().unwrap().len() as i32;
-    let mut pipes = Pipes::new();
-    let mut start: Option<Vector2D> = None;
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let pos = Vector2D::new(x as i32, y as i32);
-            if c == '.' {
-                // ground, skip
-            } else if c == 'S' {
-                // start position
-                start = Some(pos);
-            } else {
-                // pipe
-                pipes.insert(pos, Pipe::from(c));
-            }
-        }
-    }
-    let start = start.unwrap();
+fn parse(input: &str) -> Result<Input, ParseError> {
+    parse_input(input).map_err(|err| err.locate(10, input))
+}
+
+fn parse_input(input: &str) -> Result<Input, ParseError> {
+    let tiles = Grid::parse(input, "pipe", |c| match c {
+        '.' | 'S' => Some(c),
+        _ => Pipe::parse(c).map(|_| c),
+    })?;
+    let start = tiles
+        .find(|&c| c == 'S')
+        .ok_or_else(|| ParseError::expected_after(input, "start position"))?;
+    let mut pipes = tiles.map(|&c| Pipe::parse(c));
     let start_directions = Direction::all()
         .into_iter()
         .filter(|dir| {
-            if let Some(neighbour_pipe) = pipes.get(&(start + dir.step())) {
+            if let Some(Some(neighbour_pipe)) = pipes.get(start + dir.step()) {
                 let neighbour_dirs = neighbour_pipe.neighbours();
                 neighbour_dirs.contains(&dir.opposite())
             } else {
@@ -118,15 +107,15 @@ fn parse(input: &str) -> Input {
             }
         })
         .collect::<Vec<_>>();
-    let start_pipe = Pipe::from_neighbours(start_directions.try_into().unwrap());
-    pipes.insert(start, start_pipe);
+    let start_directions = start_directions.try_into().map_err(|_| {
+        let line = input.lines().nth(start.y() as usize).unwrap();
+        let (i, _) = line.char_indices().nth(start.x() as usize).unwrap();
+        ParseError::expected(&line[i..], "start with exactly two connecting pipes")
+    })?;
+    let start_pipe = Pipe::from_neighbours(start_directions);
+    pipes[start] = Some(start_pipe);
 
-    Input {
-        width,
-        height,
-        pipes,
-        start,
-    }
+    Ok(Input { pipes, start })
 }
 
 #[derive(Debug)]
@@ -139,7 +128,7 @@ struct State {
 impl State {
     fn step(self, pipes: &Pipes) -> Self {
         let next_pos = self.pos + self.out_dir.step();
-        let next_pipe = pipes.get(&next_pos).unwrap();
+        let next_pipe = pipes[next_pos].unwrap();
         let in_dir = self.out_dir.opposite();
         let out_dir = next_pipe.other_neighbour(in_dir);
         Self {
@@ -152,7 +141,7 @@ impl State {
 
 #[aoc(day10, part1)]
 fn part1(input: &Input) -> u32 {
-    let start_neighbours = input.pipes.get(&input.start).unwrap().neighbours();
+    let start_neighbours = input.pipes[input.start].unwrap().neighbours();
     let mut states = [
         State {
             pos: input.start,
@@ -176,16 +165,17 @@ fn part1(input: &Input) -> u32 {
     steps
 }
 
-fn find_main_loop(input: &Input) -> HashSet<Vector2D> {
-    let mut main_loop = HashSet::new();
-    let start_neighbours = input.pipes.get(&input.start).unwrap().neighbours();
+/// Finds the positions of all pipes in the main loop, in order.
+fn find_main_loop(input: &Input) -> Vec<Vector2D> {
+    let mut main_loop = Vec::new();
+    let start_neighbours = input.pipes[input.start].unwrap().neighbours();
     let mut state = State {
         pos: input.start,
         in_dir: start_neighbours[0],
         out_dir: start_neighbours[1],
     };
     loop {
-        main_loop.insert(state.pos);
+        main_loop.push(state.pos);
         state = state.step(&input.pipes);
         if state.pos == input.start {
             break;
@@ -196,75 +186,18 @@ fn find_main_loop(input: &Input) -> HashSet<Vector2D> {
 
 #[aoc(day10, part2)]
 fn part2(input: &Input) -> u32 {
-    let main_loop = find_main_loop(input);
-    let mut in_loop = HashSet::<Vector2D>::new();
-    for y in 0..input.height {
-        // Sweep from left to right.
-        let mut inside = false;
-        let mut previous_bend = None;
-        for x in 0..input.width {
-            let pos = Vector2D::new(x, y);
-            // If we cross the main loop...
-            if main_loop.contains(&pos) {
-                match *input.pipes.get(&pos).unwrap() {
-                    Pipe::Vert => {
-                        // We crossed the main loop.
-                        assert!(previous_bend.is_none());
-                        inside = !inside;
-                    }
-                    bend @ (Pipe::F | Pipe::L) => {
-                        // We start following along the main loop.
-                        assert!(previous_bend.is_none());
-                        previous_bend = Some(bend);
-                    }
-                    Pipe::Hori => {
-                        // We're still following along the main loop.
-                        assert!(previous_bend.is_some());
-                    }
-                    bend @ (Pipe::Seven | Pipe::J) => {
-                        // We stop following the main loop.
-                        let previous_bend = previous_bend.take().expect("mismatched bend");
-                        inside = match (previous_bend, bend) {
-                            (Pipe::F, Pipe::Seven) | (Pipe::L, Pipe::J) => {
-                                // We followed without crossing.
-                                inside
-                            }
-                            (Pipe::F, Pipe::J) | (Pipe::L, Pipe::Seven) => {
-                                // We crossed the main loop.
-                                !inside
-                            }
-                            _ => panic!("illegal bend from {previous_bend:?} to {bend:?}"),
-                        };
-                    }
-                }
-            } else if inside {
-                in_loop.insert(pos);
-            }
-        }
-        // At the end of the line, we should be outside of the main loop again.
-        assert!(!inside);
-    }
-    in_loop.len() as u32
-}
-
-impl Direction {
-    fn rotate_left(self) -> Self {
-        match self {
-            Direction::N => Direction::W,
-            Direction::W => Direction::S,
-            Direction::S => Direction::E,
-            Direction::E => Direction::N,
-        }
-    }
+    // The enclosed tiles are exactly the lattice points inside the main loop
+    let main_loop = Polygon::new(find_main_loop(input));
+    main_loop.interior_points() as u32
 }
 
 #[allow(unused)]
 fn part2_original(input: &Input) -> u32 {
-    let main_loop = find_main_loop(input);
+    let main_loop = find_main_loop(input).into_iter().collect::<HashSet<_>>();
     let mut in_loop = HashSet::<Vector2D>::new();
     // Start in the top-left corner of the loop.
     let start = *main_loop.iter().min().unwrap();
-    assert_eq!(input.pipes.get(&start), Some(&Pipe::F));
+    assert_eq!(input.pipes[start], Some(Pipe::F));
     // Follow main loop in clockwise direction.
     let mut state = State {
         pos: start,
@@ -275,7 +208,7 @@ fn part2_original(input: &Input) -> u32 {
         // Find ground tiles "inside" the loop,
         // starting from this loop pipe and working our way
         // to the other side of the loop.
-        let mut inside_dir = state.in_dir.rotate_left();
+        let mut inside_dir = state.in_dir.turn_left();
         while inside_dir != state.out_dir {
             // Mark everything, until we hit the main loop again.
             let step = inside_dir.step();
@@ -285,7 +218,7 @@ fn part2_original(input: &Input) -> u32 {
                 inside_pos += step;
             }
             // Rotate left, within the inside of the shape.
-            inside_dir = inside_dir.rotate_left();
+            inside_dir = inside_dir.turn_left();
         }
         // Move along the main loop
         state = state.step(&input.pipes);
@@ -313,12 +246,12 @@ LJ.LJ";
 
     #[test]
     fn part1_square_loop() {
-        assert_eq!(part1(&parse(SQUARE_LOOP)), 4);
+        assert_eq!(part1(&parse(SQUARE_LOOP).unwrap()), 4);
     }
 
     #[test]
     fn part1_complex_loop() {
-        assert_eq!(part1(&parse(COMPLEX_LOOP)), 8);
+        assert_eq!(part1(&parse(COMPLEX_LOOP).unwrap()), 8);
     }
 
     const ENCLOSED1: &str = "..........
@@ -353,16 +286,16 @@ L7JLJL-JLJLJL--JLJ.L";
 
     #[test]
     fn part2_example1() {
-        assert_eq!(part2(&parse(ENCLOSED1)), 4);
+        assert_eq!(part2(&parse(ENCLOSED1).unwrap()), 4);
     }
 
     #[test]
     fn part2_example2() {
-        assert_eq!(part2(&parse(ENCLOSED2)), 8);
+        assert_eq!(part2(&parse(ENCLOSED2).unwrap()), 8);
     }
 
     #[test]
     fn part2_example3() {
-        assert_eq!(part2(&parse(ENCLOSED3)), 10);
+        assert_eq!(part2(&parse(ENCLOSED3).unwrap()), 10);
     }
 }
@@ -1,6 +1,6 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::Vector2D;
+use crate::util::{Grid, ParseError, Vector2D};
 
 #[derive(Debug, Clone)]
 struct Image {
@@ -10,27 +10,22 @@ struct Image {
 }
 
 #[aoc_generator(day11)]
-fn parse(input: &str) -> Image {
-    let width = input.lines().count() as i32;
-    let height = input.lines().next().unwrap().len() as i32;
-    let galaxies = input
-        .lines()
-        .enumerate()
-        .flat_map(move |(y, line)| {
-            line.chars().enumerate().filter_map(move |(x, c)| {
-                if c == '#' {
-                    Some(Vector2D::new(x as i32, y as i32))
-                } else {
-                    None
-                }
-            })
-        })
+fn parse(input: &str) -> Result<Image, ParseError> {
+    let grid = Grid::parse(input, "galaxy or empty space", |c| match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    })
+    .map_err(|err| err.locate(11, input))?;
+    let galaxies = grid
+        .iter()
+        .filter_map(|(pos, &galaxy)| if galaxy { Some(pos) } else { None })
         .collect();
-    Image {
-        width,
-        height,
+    Ok(Image {
+        width: grid.width() as i32,
+        height: grid.height() as i32,
         galaxies,
-    }
+    })
 }
 
 impl Image {
@@ -98,18 +93,18 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 374);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 374);
     }
 
     #[test]
     fn part2_example1() {
-        let input = parse(INPUT);
+        let input = parse(INPUT).unwrap();
         assert_eq!(input.expand(10).total_distance(), 1030);
     }
 
     #[test]
     fn part2_example2() {
-        let input = parse(INPUT);
+        let input = parse(INPUT).unwrap();
         assert_eq!(input.expand(100).total_distance(), 8410);
     }
 }
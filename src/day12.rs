@@ -1,8 +1,11 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{parse_lines, parse_number, split_once, Grid, ParseError, Vector2D};
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum Spring {
     Unknown,
@@ -16,31 +19,39 @@ struct Record {
     groups: Vec<usize>,
 }
 
-impl From<char> for Spring {
-    fn from(c: char) -> Self {
-        match c {
+impl Spring {
+    fn parse(c: char) -> Option<Spring> {
+        Some(match c {
             '?' => Spring::Unknown,
             '.' => Spring::Operational,
             '#' => Spring::Damaged,
-            _ => panic!("invalid spring {c}"),
-        }
+            _ => return None,
+        })
     }
 }
 
 impl FromStr for Record {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (springs, groups) = s.split_once(' ').unwrap();
-        let springs = springs.chars().map(Spring::from).collect();
-        let groups = groups.split(',').map(|x| x.parse().unwrap()).collect();
+        let (springs, groups) = split_once(s, " ")?;
+        let springs = springs
+            .char_indices()
+            .map(|(i, c)| {
+                Spring::parse(c).ok_or_else(|| ParseError::expected(&springs[i..], "spring"))
+            })
+            .collect::<Result<_, _>>()?;
+        let groups = groups
+            .split(',')
+            .map(parse_number)
+            .collect::<Result<_, _>>()?;
         Ok(Self { springs, groups })
     }
 }
 
 #[aoc_generator(day12)]
-fn parse(input: &str) -> Vec<Record> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Record>, ParseError> {
+    parse_lines(input).map_err(|err| err.locate(12, input))
 }
 
 fn match_group(springs: &[Spring], group: usize) -> Option<&[Spring]> {
@@ -79,7 +90,7 @@ fn solve<'a>(
         };
     }
     if groups.is_empty() {
-        return if springs.iter().any(|&spring| spring == Spring::Damaged) {
+        return if springs.contains(&Spring::Damaged) {
             // At least one damaged spring is not in a group
             0
         } else {
@@ -115,12 +126,100 @@ fn solve<'a>(
     matches
 }
 
+/// Adds every way to fill in the unknown `springs` to `result`, each prefixed by `current`.
+///
+/// Like `solve`, this only looks at suffixes of the same springs and groups,
+/// so both share one cache for the whole record.
+fn arrange<'a>(
+    springs: &'a [Spring],
+    groups: &'a [usize],
+    current: &mut Vec<Spring>,
+    result: &mut Vec<Vec<Spring>>,
+    cache: &mut HashMap<(&'a [Spring], &'a [usize]), usize>,
+) {
+    // Stop as soon as the rest can no longer be filled in
+    if solve(springs, groups, cache) == 0 {
+        return;
+    }
+    let prefix_len = current.len();
+    let Some((&first_group, remaining_groups)) = groups.split_first() else {
+        current.resize(prefix_len + springs.len(), Spring::Operational);
+        result.push(current.clone());
+        current.truncate(prefix_len);
+        return;
+    };
+    // Try every place for the next group, up to the first damaged spring
+    for start in 0..springs.len() {
+        if let Some(remaining_springs) = match_group(&springs[start..], first_group) {
+            current.resize(prefix_len + start, Spring::Operational);
+            current.resize(prefix_len + start + first_group, Spring::Damaged);
+            // Along with the operational spring that ends the group, if any
+            current.resize(
+                prefix_len + springs.len() - remaining_springs.len(),
+                Spring::Operational,
+            );
+            arrange(remaining_springs, remaining_groups, current, result, cache);
+            current.truncate(prefix_len);
+        }
+        if springs[start] == Spring::Damaged {
+            break;
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Record {
+    /// The number of ways to fill in the unknown springs.
+    fn count(&self) -> usize {
+        solve(&self.springs, &self.groups, &mut HashMap::new())
+    }
+
+    /// All ways to fill in the unknown springs.
+    fn arrangements(&self) -> Vec<Vec<Spring>> {
+        let mut arrangements = vec![];
+        let mut cache = HashMap::new();
+        arrange(
+            &self.springs,
+            &self.groups,
+            &mut vec![],
+            &mut arrangements,
+            &mut cache,
+        );
+        arrangements
+    }
+
+    /// For every spring: its state if it is the same in every arrangement, or unknown otherwise.
+    /// Returns `None` if there are no arrangements at all.
+    fn forced(&self) -> Option<Vec<Spring>> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let mut record = self.clone();
+        let forced = (0..self.springs.len())
+            .map(|index| {
+                if self.springs[index] != Spring::Unknown {
+                    return self.springs[index];
+                }
+                record.springs[index] = Spring::Damaged;
+                let damaged = record.count();
+                record.springs[index] = Spring::Unknown;
+                if damaged == 0 {
+                    Spring::Operational
+                } else if damaged == total {
+                    Spring::Damaged
+                } else {
+                    Spring::Unknown
+                }
+            })
+            .collect();
+        Some(forced)
+    }
+}
+
 #[aoc(day12, part1)]
 fn part1(input: &[Record]) -> usize {
-    input
-        .iter()
-        .map(|record| solve(&record.springs, &record.groups, &mut HashMap::new()))
-        .sum()
+    input.iter().map(Record::count).sum()
 }
 
 #[aoc(day12, part2)]
@@ -143,6 +242,126 @@ fn part2(input: &[Record]) -> usize {
     part1(&new_input)
 }
 
+impl Display for Spring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let c = match self {
+            Spring::Unknown => '?',
+            Spring::Operational => '.',
+            Spring::Damaged => '#',
+        };
+        write!(f, "{c}")
+    }
+}
+
+/// A nonogram puzzle: fill in a grid so that every row and column has the given groups
+/// of consecutive damaged cells, just like a row of springs.
+///
+/// See: https://en.wikipedia.org/wiki/Nonogram
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    columns: Vec<Vec<usize>>,
+}
+
+#[allow(dead_code)]
+impl Nonogram {
+    fn new(rows: Vec<Vec<usize>>, columns: Vec<Vec<usize>>) -> Self {
+        Self { rows, columns }
+    }
+
+    /// Fills in every cell that is forced by its row or column, until nothing changes.
+    /// Returns `false` if some row or column can no longer be satisfied.
+    fn propagate(&self, grid: &mut Grid<Spring>) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, groups) in self.rows.iter().enumerate() {
+                let positions = (0..grid.width()).map(|x| Vector2D::new(x as i32, y as i32));
+                let Some(line_changed) = Self::propagate_line(grid, positions, groups) else {
+                    return false;
+                };
+                changed |= line_changed;
+            }
+            for (x, groups) in self.columns.iter().enumerate() {
+                let positions = (0..grid.height()).map(|y| Vector2D::new(x as i32, y as i32));
+                let Some(line_changed) = Self::propagate_line(grid, positions, groups) else {
+                    return false;
+                };
+                changed |= line_changed;
+            }
+        }
+        true
+    }
+
+    fn propagate_line(
+        grid: &mut Grid<Spring>,
+        positions: impl Iterator<Item = Vector2D>,
+        groups: &[usize],
+    ) -> Option<bool> {
+        let positions = positions.collect::<Vec<_>>();
+        let record = Record {
+            springs: positions.iter().map(|&pos| grid[pos]).collect(),
+            groups: groups.to_vec(),
+        };
+        if !record.springs.contains(&Spring::Unknown) {
+            return (record.count() == 1).then_some(false);
+        }
+        let forced = record.forced()?;
+        let changed = forced != record.springs;
+        for (pos, spring) in positions.into_iter().zip(forced) {
+            grid[pos] = spring;
+        }
+        Some(changed)
+    }
+
+    /// Calls `visit` for every solution, until it returns `false`.
+    fn search(
+        &self,
+        mut grid: Grid<Spring>,
+        visit: &mut impl FnMut(&Grid<Spring>) -> bool,
+    ) -> bool {
+        if !self.propagate(&mut grid) {
+            return true;
+        }
+        // When propagation gets stuck, guess the first unknown cell
+        let Some(pos) = grid.find(|&spring| spring == Spring::Unknown) else {
+            return visit(&grid);
+        };
+        for spring in [Spring::Damaged, Spring::Operational] {
+            let mut guess = grid.clone();
+            guess[pos] = spring;
+            if !self.search(guess, visit) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Finds up to `limit` solutions.
+    fn solutions(&self, limit: usize) -> Vec<Grid<Spring>> {
+        let mut solutions = vec![];
+        if limit > 0 {
+            let grid = Grid::new(self.columns.len(), self.rows.len(), Spring::Unknown);
+            self.search(grid, &mut |solution| {
+                solutions.push(solution.clone());
+                solutions.len() < limit
+            });
+        }
+        solutions
+    }
+
+    fn count_solutions(&self) -> usize {
+        let mut count = 0;
+        let grid = Grid::new(self.columns.len(), self.rows.len(), Spring::Unknown);
+        self.search(grid, &mut |_| {
+            count += 1;
+            true
+        });
+        count
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,11 +375,70 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 21);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 21);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 525152);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 525152);
+    }
+
+    fn format(springs: &[Spring]) -> String {
+        springs.iter().map(Spring::to_string).collect()
+    }
+
+    #[test]
+    fn arrangements() {
+        let record = "?###???????? 3,2,1".parse::<Record>().unwrap();
+        let arrangements = record.arrangements();
+        assert_eq!(arrangements.len(), record.count());
+        assert_eq!(arrangements.len(), 10);
+        assert_eq!(format(&arrangements[0]), ".###.##.#...");
+        assert_eq!(format(&arrangements[9]), ".###....##.#");
+        let record = "?#?.? 1,1".parse::<Record>().unwrap();
+        let arrangements = record
+            .arrangements()
+            .iter()
+            .map(|a| format(a))
+            .collect::<Vec<_>>();
+        assert_eq!(arrangements, [".#..#"]);
+    }
+
+    #[test]
+    fn forced_springs() {
+        let forced = |s: &str| s.parse::<Record>().unwrap().forced();
+        assert_eq!(format(&forced("??????? 2,1,2").unwrap()), "##.#.##");
+        assert_eq!(format(&forced("???.### 1,1,3").unwrap()), "#.#.###");
+        assert_eq!(format(&forced("?????? 2,2").unwrap()), "?#??#?");
+        assert_eq!(format(&forced("??? 1").unwrap()), "???");
+        assert_eq!(forced("#.# 2"), None);
+    }
+
+    #[test]
+    fn nonogram() {
+        // A heart
+        let nonogram = Nonogram::new(
+            vec![vec![1, 1], vec![5], vec![5], vec![3], vec![1]],
+            vec![vec![2], vec![4], vec![4], vec![4], vec![2]],
+        );
+        let solutions = nonogram.solutions(usize::MAX);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(
+            solutions[0].to_string(),
+            ".#.#.\n#####\n#####\n.###.\n..#.."
+        );
+        assert_eq!(nonogram.count_solutions(), 1);
+    }
+
+    #[test]
+    fn nonogram_backtracking() {
+        // Propagation alone can't decide between the two diagonals
+        let nonogram = Nonogram::new(vec![vec![1], vec![1]], vec![vec![1], vec![1]]);
+        assert_eq!(nonogram.count_solutions(), 2);
+        let solutions = nonogram.solutions(1);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].to_string(), "#.\n.#");
+        let nonogram = Nonogram::new(vec![vec![2], vec![]], vec![vec![2], vec![]]);
+        assert_eq!(nonogram.count_solutions(), 0);
     }
 }
@@ -1,113 +1,163 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{Grid, ParseError, Vector2D};
+
 #[derive(Debug, Clone)]
 struct Pattern {
-    width: usize,
-    height: usize,
-    cells: Vec<Vec<char>>,
+    cells: Grid<char>,
 }
 
 impl FromStr for Pattern {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let height = s.lines().count();
-        let width = s.lines().next().unwrap().len();
-        let cells = s.lines().map(|line| line.chars().collect()).collect();
-        Ok(Self {
-            width,
-            height,
-            cells,
-        })
+        let cells = Grid::parse(s, "ash or rock", |c| match c {
+            '.' | '#' => Some(c),
+            _ => None,
+        })?;
+        Ok(Self { cells })
     }
 }
 
 #[aoc_generator(day13)]
-fn parse(input: &str) -> Vec<Pattern> {
-    input.split("\n\n").map(|s| s.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Pattern>, ParseError> {
+    input
+        .split("\n\n")
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|err: ParseError| err.locate(13, input))
 }
 
-impl Pattern {
-    fn is_vertical_mirror(&self, mirror: usize) -> bool {
-        let max_offset = mirror.min(self.width.saturating_sub(mirror));
-        for dx in 0..max_offset {
-            for y in 0..self.height {
-                if self.cells[y][mirror - dx - 1] != self.cells[y][mirror + dx] {
-                    return false;
-                }
-            }
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Axis {
+    /// A vertical line between two columns.
+    Vertical,
+    /// A horizontal line between two rows.
+    Horizontal,
+}
+
+/// A candidate mirror line, with the cells that don't match their reflection.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Reflection {
+    axis: Axis,
+    /// The number of columns left of a vertical mirror, or rows above a horizontal one.
+    position: usize,
+    /// For each mismatched pair of cells: the one before the mirror.
+    /// Flipping either cell of a pair would fix it.
+    smudges: Vec<Vector2D>,
+}
+
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Vertical => self.position,
+            Axis::Horizontal => self.position * 100,
         }
-        true
     }
+}
 
-    fn is_horizontal_mirror(&self, mirror: usize) -> bool {
-        let max_offset = mirror.min(self.height.saturating_sub(mirror));
-        for dy in 0..max_offset {
-            if self.cells[mirror - dy - 1] != self.cells[mirror + dy] {
-                return false;
-            }
-        }
-        true
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct NoReflection {
+    pattern: usize,
+    smudges: usize,
+}
+
+impl Display for NoReflection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no mirror with {} smudges found in pattern #{}",
+            self.smudges, self.pattern
+        )
     }
+}
 
-    fn solve(&self, prev_solution: Option<usize>) -> Option<usize> {
-        for mirror in 1..self.width {
-            if self.is_vertical_mirror(mirror) && prev_solution != Some(mirror) {
-                return Some(mirror);
-            }
-        }
-        for mirror in 1..self.height {
-            if self.is_horizontal_mirror(mirror) && prev_solution != Some(mirror * 100) {
-                return Some(mirror * 100);
-            }
-        }
-        None
+impl Error for NoReflection {}
+
+impl Pattern {
+    fn width(&self) -> usize {
+        self.cells.width()
+    }
+
+    fn height(&self) -> usize {
+        self.cells.height()
     }
 
-    fn flip(mut self, index: usize) -> Self {
-        let cell = &mut self.cells[index / self.width][index % self.width];
-        *cell = match *cell {
-            '.' => '#',
-            '#' => '.',
-            _ => panic!("invalid cell"),
+    fn reflect(&self, axis: Axis, position: usize) -> Reflection {
+        let (x_len, y_len) = match axis {
+            Axis::Vertical => (self.width(), self.height()),
+            Axis::Horizontal => (self.height(), self.width()),
         };
-        self
+        // Compare every pair of lines on both sides of the mirror, until either side runs out
+        let max_offset = position.min(x_len - position);
+        let smudges = (0..max_offset)
+            .flat_map(|offset| {
+                (0..y_len).filter_map(move |y| {
+                    let (before, after) = match axis {
+                        Axis::Vertical => (
+                            Vector2D::new((position - offset - 1) as i32, y as i32),
+                            Vector2D::new((position + offset) as i32, y as i32),
+                        ),
+                        Axis::Horizontal => (
+                            Vector2D::new(y as i32, (position - offset - 1) as i32),
+                            Vector2D::new(y as i32, (position + offset) as i32),
+                        ),
+                    };
+                    (self.cells[before] != self.cells[after]).then_some(before)
+                })
+            })
+            .collect();
+        Reflection {
+            axis,
+            position,
+            smudges,
+        }
+    }
+
+    /// All possible mirror lines, first the vertical ones from left to right,
+    /// then the horizontal ones from top to bottom.
+    fn reflections(&self) -> impl Iterator<Item = Reflection> + '_ {
+        let vertical = (1..self.width()).map(|position| self.reflect(Axis::Vertical, position));
+        let horizontal =
+            (1..self.height()).map(|position| self.reflect(Axis::Horizontal, position));
+        vertical.chain(horizontal)
+    }
+
+    /// Finds the first mirror line where exactly `smudges` cells don't match.
+    fn find_reflection(&self, smudges: usize) -> Option<Reflection> {
+        self.reflections()
+            .find(|reflection| reflection.smudges.len() == smudges)
     }
 }
 
-#[aoc(day13, part1)]
-fn part1(input: &[Pattern]) -> usize {
+fn summarize(input: &[Pattern], smudges: usize) -> Result<usize, NoReflection> {
     input
         .iter()
         .enumerate()
-        .map(|(i, pattern)| {
-            pattern.solve(None).unwrap_or_else(|| {
-                panic!("no mirror found in pattern #{i}");
-            })
+        .map(|(index, pattern)| {
+            pattern
+                .find_reflection(smudges)
+                .map(|reflection| reflection.summary())
+                .ok_or(NoReflection {
+                    pattern: index,
+                    smudges,
+                })
         })
         .sum()
 }
 
+#[aoc(day13, part1)]
+fn part1(input: &[Pattern]) -> Result<usize, NoReflection> {
+    summarize(input, 0)
+}
+
 #[aoc(day13, part2)]
-fn part2(input: &[Pattern]) -> usize {
-    input
-        .iter()
-        .enumerate()
-        .map(|(i, pattern)| {
-            let prev_solution = pattern.solve(None).unwrap_or_else(|| {
-                panic!("no mirror found in pattern #{i}");
-            });
-            for index in 0..(pattern.width * pattern.height) {
-                let pattern = pattern.clone().flip(index);
-                if let Some(new_solution) = pattern.solve(Some(prev_solution)) {
-                    return new_solution;
-                }
-            }
-            panic!("no smudge found in pattern #{i}");
-        })
-        .sum()
+fn part2(input: &[Pattern]) -> Result<usize, NoReflection> {
+    summarize(input, 1)
 }
 
 #[cfg(test)]
@@ -132,11 +182,43 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 405);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(405));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 400);
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(400));
+    }
+
+    #[test]
+    fn reflections() {
+        let patterns = parse(INPUT).unwrap();
+        let reflections = patterns[0].reflections().collect::<Vec<_>>();
+        assert_eq!(reflections.len(), 8 + 6);
+        assert_eq!(reflections[4].axis, Axis::Vertical);
+        assert_eq!(reflections[4].position, 5);
+        assert_eq!(reflections[4].smudges, vec![]);
+        let smudged = patterns[0].find_reflection(1).unwrap();
+        assert_eq!(smudged.axis, Axis::Horizontal);
+        assert_eq!(smudged.position, 3);
+        assert_eq!(smudged.smudges, vec![Vector2D::new(0, 0)]);
+        assert_eq!(patterns[1].find_reflection(1).unwrap().summary(), 100);
+        assert_eq!(
+            patterns[1].find_reflection(1).unwrap().smudges,
+            vec![Vector2D::new(4, 0)]
+        );
+    }
+
+    #[test]
+    fn no_reflection() {
+        let patterns = parse("#.\n..").unwrap();
+        assert_eq!(
+            part1(&patterns),
+            Err(NoReflection {
+                pattern: 0,
+                smudges: 0
+            })
+        );
+        assert_eq!(patterns[0].find_reflection(1).unwrap().summary(), 1);
     }
 }
@@ -1,174 +1,163 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::Vector2D;
+use crate::util::{state_at, Direction, Grid, ParseError};
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// A platform with rocks, stored as one bitset per row where bit `x` is column `x`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct Platform {
-    width: i32,
-    height: i32,
-    cubes: HashSet<Vector2D>,
-    rounds: Vec<Vector2D>,
+    width: usize,
+    height: usize,
+    cubes: Vec<u128>,
+    rounds: Vec<u128>,
 }
 
 #[aoc_generator(day14)]
-fn parse(input: &str) -> Platform {
-    let width = input.lines().count() as i32;
-    let height = input.lines().next().unwrap().len() as i32;
-    let mut cubes = HashSet::new();
-    let mut rounds = Vec::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            match c {
-                '.' => {}
-                '#' => {
-                    cubes.insert(Vector2D::new(x as i32, y as i32));
-                }
-                'O' => {
-                    rounds.push(Vector2D::new(x as i32, y as i32));
-                }
-                _ => panic!("invalid input {c}"),
-            };
-        }
-    }
-    Platform {
-        width,
-        height,
-        cubes,
-        rounds,
+fn parse(input: &str) -> Result<Platform, ParseError> {
+    let grid = Grid::parse(input, "rock or empty space", |c| match c {
+        '.' | '#' | 'O' => Some(c),
+        _ => None,
+    })
+    .map_err(|err| err.locate(14, input))?;
+    if grid.width() > u128::BITS as usize {
+        return Err(ParseError::expected(input, "at most 128 columns").locate(14, input));
     }
+    let row_bits = |rock: char| {
+        grid.rows()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .filter(|&(_, &c)| c == rock)
+                    .fold(0u128, |bits, (x, _)| bits | 1 << x)
+            })
+            .collect()
+    };
+    Ok(Platform {
+        width: grid.width(),
+        height: grid.height(),
+        cubes: row_bits('#'),
+        rounds: row_bits('O'),
+    })
 }
 
-fn compare_rounds(left: &Vector2D, right: &Vector2D) -> Ordering {
-    left.y()
-        .cmp(&right.y())
-        .then_with(|| left.x().cmp(&right.x()))
-}
+/// One spin cycle tilts the platform north, then west, then south, then east.
+const SPIN_CYCLE: [Direction; 4] = [Direction::N, Direction::W, Direction::S, Direction::E];
 
 impl Platform {
-    fn roll_north(&mut self) {
-        for i in 0..self.rounds.len() {
-            let mut round = self.rounds[i];
-            loop {
-                let next_pos = round + Vector2D::new(0, -1);
-                if next_pos.y() >= 0
-                    && !self.cubes.contains(&next_pos)
-                    && !self.rounds.contains(&next_pos)
-                {
-                    round = next_pos;
-                } else {
-                    break;
-                }
+    fn row_mask(&self) -> u128 {
+        // Shifting by all 128 bits would overflow, which happens for an empty platform
+        u128::MAX
+            .checked_shr(u128::BITS - self.width as u32)
+            .unwrap_or(0)
+    }
+
+    /// Rolls all round rocks to the first row (north) or the last row (south).
+    fn roll_vertical(&mut self, north: bool) {
+        // Go through the rows starting from the edge, so rocks stack up on rocks that already rolled
+        let row_at = |i: usize| if north { i } else { self.height - 1 - i };
+        for i in 1..self.height {
+            let mut moving = std::mem::take(&mut self.rounds[row_at(i)]);
+            let mut j = i;
+            while moving != 0 && j > 0 {
+                let next_row = row_at(j - 1);
+                let blocked = moving & (self.rounds[next_row] | self.cubes[next_row]);
+                self.rounds[row_at(j)] |= blocked;
+                moving &= !blocked;
+                j -= 1;
             }
-            self.rounds[i] = round;
+            self.rounds[row_at(j)] |= moving;
         }
-        self.rounds.sort_by(compare_rounds);
     }
 
-    fn roll_south(&mut self) {
-        for i in (0..self.rounds.len()).rev() {
-            let mut round = self.rounds[i];
+    /// Rolls all round rocks to the first column (west) or the last column (east).
+    fn roll_horizontal(&mut self, west: bool) {
+        let mask = self.row_mask();
+        for (rounds, &cubes) in self.rounds.iter_mut().zip(&self.cubes) {
+            // Move every rock with free space next to it, until none can move
             loop {
-                let next_pos = round + Vector2D::new(0, 1);
-                if next_pos.y() < self.height
-                    && !self.cubes.contains(&next_pos)
-                    && !self.rounds.contains(&next_pos)
-                {
-                    round = next_pos;
+                let free = !(*rounds | cubes) & mask;
+                let movable = if west {
+                    *rounds & (free << 1)
                 } else {
+                    *rounds & (free >> 1)
+                };
+                if movable == 0 {
                     break;
                 }
+                let moved = if west { movable >> 1 } else { movable << 1 };
+                *rounds = (*rounds & !movable) | moved;
             }
-            self.rounds[i] = round;
         }
-        self.rounds.sort_by(compare_rounds);
     }
 
-    fn roll_west(&mut self) {
-        for i in 0..self.rounds.len() {
-            let mut round = self.rounds[i];
-            loop {
-                let next_pos = round + Vector2D::new(-1, 0);
-                if next_pos.x() >= 0
-                    && !self.cubes.contains(&next_pos)
-                    && !self.rounds.contains(&next_pos)
-                {
-                    round = next_pos;
-                } else {
-                    break;
-                }
-            }
-            self.rounds[i] = round;
+    fn tilt(&mut self, dir: Direction) {
+        match dir {
+            Direction::N => self.roll_vertical(true),
+            Direction::S => self.roll_vertical(false),
+            Direction::W => self.roll_horizontal(true),
+            Direction::E => self.roll_horizontal(false),
         }
-        self.rounds.sort_by(compare_rounds);
     }
 
-    fn roll_east(&mut self) {
-        for i in (0..self.rounds.len()).rev() {
-            let mut round = self.rounds[i];
-            loop {
-                let next_pos = round + Vector2D::new(1, 0);
-                if next_pos.x() < self.width
-                    && !self.cubes.contains(&next_pos)
-                    && !self.rounds.contains(&next_pos)
-                {
-                    round = next_pos;
-                } else {
-                    break;
-                }
-            }
-            self.rounds[i] = round;
+    fn run(&mut self, program: &[Direction]) {
+        for &dir in program {
+            self.tilt(dir);
         }
-        self.rounds.sort_by(compare_rounds);
     }
 
-    fn spin_cycle(&mut self) {
-        self.roll_north();
-        self.roll_west();
-        self.roll_south();
-        self.roll_east();
+    /// Runs the program `times` times, skipping ahead once the rocks start repeating.
+    fn run_repeated(&self, program: &[Direction], times: usize) -> Platform {
+        state_at(
+            self.clone(),
+            |platform| {
+                let mut platform = platform.clone();
+                platform.run(program);
+                platform
+            },
+            times,
+        )
     }
 
-    fn total_load_north(&self) -> i32 {
-        self.rounds
-            .iter()
-            .map(|round| self.height - round.y())
-            .sum()
+    /// The total load on the support beams along the given edge:
+    /// each round rock counts for its distance to the opposite edge, plus one.
+    fn load(&self, edge: Direction) -> usize {
+        match edge {
+            Direction::N | Direction::S => (0..self.height)
+                .map(|y| {
+                    let weight = if edge == Direction::N {
+                        self.height - y
+                    } else {
+                        y + 1
+                    };
+                    weight * self.rounds[y].count_ones() as usize
+                })
+                .sum(),
+            Direction::W | Direction::E => (0..self.width)
+                .map(|x| {
+                    let weight = if edge == Direction::W {
+                        self.width - x
+                    } else {
+                        x + 1
+                    };
+                    let count = self.rounds.iter().filter(|&&row| row & 1 << x != 0).count();
+                    weight * count
+                })
+                .sum(),
+        }
     }
 }
 
 #[aoc(day14, part1)]
-fn part1(input: &Platform) -> i32 {
+fn part1(input: &Platform) -> usize {
     let mut platform = input.clone();
-    platform.roll_north();
-    platform.total_load_north()
+    platform.tilt(Direction::N);
+    platform.load(Direction::N)
 }
 
 #[aoc(day14, part2)]
-fn part2(input: &Platform) -> i32 {
-    let target = 1_000_000_000u64;
-    let mut platform = input.clone();
-    let mut seen = HashMap::new();
-    let mut count = 0u64;
-    while !seen.contains_key(&platform.rounds) {
-        seen.insert(platform.rounds.clone(), count);
-        platform.spin_cycle();
-        count += 1;
-    }
-    let loop_start = *seen.get(&platform.rounds).expect("no loop found");
-    let loop_length = count - loop_start;
-    let target_count = loop_start + ((target - loop_start) % loop_length);
-    let (target_rounds, _) = seen
-        .iter()
-        .find(|(_platform, count)| **count == target_count)
-        .unwrap_or_else(|| panic!("no platform found at step {target_count}"));
-    let target_platform = Platform {
-        rounds: target_rounds.clone(),
-        ..platform
-    };
-    target_platform.total_load_north()
+fn part2(input: &Platform) -> usize {
+    input
+        .run_repeated(&SPIN_CYCLE, 1_000_000_000)
+        .load(Direction::N)
 }
 
 #[cfg(test)]
@@ -188,11 +177,53 @@ O.#..O.#.#
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 136);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 136);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 64);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 64);
+    }
+
+    #[test]
+    fn tilt_program() {
+        let platform = parse(INPUT).unwrap();
+        let after_one_cycle = parse(
+            ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....",
+        )
+        .unwrap();
+        let mut spun = platform.clone();
+        spun.run(&SPIN_CYCLE);
+        assert_eq!(spun, after_one_cycle);
+        assert_eq!(platform.run_repeated(&SPIN_CYCLE, 1), after_one_cycle);
+        assert_eq!(
+            platform.run_repeated(&SPIN_CYCLE, 3),
+            platform.run_repeated(&SPIN_CYCLE.repeat(3), 1)
+        );
+    }
+
+    #[test]
+    fn loads() {
+        let mut platform = parse("O.#\n..O\nO..").unwrap();
+        assert_eq!(platform.load(Direction::N), 3 + 2 + 1);
+        assert_eq!(platform.load(Direction::S), 1 + 2 + 3);
+        assert_eq!(platform.load(Direction::W), 3 + 1 + 3);
+        assert_eq!(platform.load(Direction::E), 1 + 3 + 1);
+        platform.tilt(Direction::E);
+        assert_eq!(platform, parse(".O#\n..O\n..O").unwrap());
+        platform.tilt(Direction::S);
+        assert_eq!(platform, parse("..#\n..O\n.OO").unwrap());
+        let mut empty = parse("").unwrap();
+        empty.run(&SPIN_CYCLE);
+        assert_eq!(empty.load(Direction::W), 0);
     }
 }
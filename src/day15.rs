@@ -1,11 +1,30 @@
-use std::array;
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{parse_number, ParseError};
+
+/// A step of the initialization sequence, as written and as an operation.
+#[derive(Debug, Clone)]
+struct Step {
+    text: String,
+    operation: Operation,
+}
+
 #[aoc_generator(day15)]
-fn parse(input: &str) -> Vec<String> {
-    input.split(',').map(|s| s.to_string()).collect()
+fn parse(input: &str) -> Result<Vec<Step>, ParseError> {
+    input
+        .split(',')
+        .map(|s| {
+            Ok(Step {
+                text: s.to_string(),
+                operation: s.parse()?,
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map_err(|err: ParseError| err.locate(15, input))
 }
 
 fn hash(s: &str) -> u8 {
@@ -17,103 +36,190 @@ fn hash(s: &str) -> u8 {
 }
 
 #[aoc(day15, part1)]
-fn part1(input: &[String]) -> u32 {
-    input.iter().map(|s| hash(s) as u32).sum()
+fn part1(input: &[Step]) -> u32 {
+    input.iter().map(|step| hash(&step.text) as u32).sum()
 }
 
-#[derive(Debug)]
-struct Lens {
-    label: String,
-    focal_length: u32,
+/// The HASHMAP from the puzzle: a hash table where each box keeps its entries in insertion order.
+#[derive(Debug, Clone)]
+struct HashMap<V> {
+    boxes: Vec<Vec<(String, V)>>,
+    hasher: fn(&str) -> usize,
 }
 
-#[derive(Debug)]
-struct HashMap {
-    boxes: [Vec<Lens>; 256],
+/// An entry in a [`HashMap`], together with its position.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Entry<'a, V> {
+    box_index: usize,
+    slot_index: usize,
+    label: &'a str,
+    value: &'a V,
 }
 
-impl HashMap {
+impl<V> HashMap<V> {
+    /// Creates an empty map with 256 boxes, using the HASH algorithm.
     fn new() -> Self {
+        Self::with_hasher(NonZeroUsize::new(256).unwrap(), |label| {
+            hash(label) as usize
+        })
+    }
+
+    /// Creates an empty map with `box_count` boxes. A label goes into box `hasher(label)`,
+    /// modulo the number of boxes.
+    fn with_hasher(box_count: NonZeroUsize, hasher: fn(&str) -> usize) -> Self {
         Self {
-            boxes: array::from_fn(|_| Vec::new()),
+            boxes: (0..box_count.get()).map(|_| Vec::new()).collect(),
+            hasher,
         }
     }
 
-    fn remove(&mut self, label: &str) {
-        let lens_box = &mut self.boxes[hash(label) as usize];
-        if let Some(slot_index) = lens_box.iter().position(|lens| lens.label == label) {
-            lens_box.remove(slot_index);
+    fn box_of(&mut self, label: &str) -> &mut Vec<(String, V)> {
+        let box_count = self.boxes.len();
+        &mut self.boxes[(self.hasher)(label) % box_count]
+    }
+
+    fn remove(&mut self, label: &str) -> Option<V> {
+        let lens_box = self.box_of(label);
+        let slot_index = lens_box.iter().position(|(other, _)| other == label)?;
+        Some(lens_box.remove(slot_index).1)
+    }
+
+    /// Replaces the value of an existing label in place, or adds it to the back of its box.
+    fn insert(&mut self, label: String, value: V) -> Option<V> {
+        let lens_box = self.box_of(&label);
+        if let Some((_, old_value)) = lens_box.iter_mut().find(|(other, _)| *other == label) {
+            Some(std::mem::replace(old_value, value))
+        } else {
+            lens_box.push((label, value));
+            None
         }
     }
 
-    fn insert(&mut self, new_lens: Lens) {
-        let lens_box = &mut self.boxes[hash(&new_lens.label) as usize];
-        if let Some(slot_index) = lens_box
+    #[allow(dead_code)]
+    fn get(&self, label: &str) -> Option<&V> {
+        let lens_box = &self.boxes[(self.hasher)(label) % self.boxes.len()];
+        lens_box
             .iter()
-            .position(|lens| lens.label == new_lens.label)
-        {
-            lens_box[slot_index] = new_lens;
-        } else {
-            lens_box.push(new_lens);
+            .find(|(other, _)| other == label)
+            .map(|(_, value)| value)
+    }
+
+    fn apply(&mut self, operation: Operation<V>) {
+        match operation {
+            Operation::Remove(label) => {
+                self.remove(&label);
+            }
+            Operation::Insert(label, value) => {
+                self.insert(label, value);
+            }
         }
     }
 
-    fn power(&self) -> u32 {
+    /// Iterates over all entries, by box and then by slot.
+    fn iter(&self) -> impl Iterator<Item = Entry<'_, V>> {
         self.boxes
             .iter()
             .enumerate()
-            .map(move |(box_index, lens_box)| {
+            .flat_map(|(box_index, lens_box)| {
                 lens_box
                     .iter()
                     .enumerate()
-                    .map(move |(slot_index, lens)| {
-                        (box_index as u32 + 1) * (slot_index as u32 + 1) * lens.focal_length
+                    .map(move |(slot_index, (label, value))| Entry {
+                        box_index,
+                        slot_index,
+                        label,
+                        value,
                     })
-                    .sum::<u32>()
             })
-            .sum()
+    }
+
+    /// Sums the score of every entry.
+    fn score(&self, scoring: impl Fn(&Entry<V>) -> u32) -> u32 {
+        self.iter().map(|entry| scoring(&entry)).sum()
     }
 }
 
-enum Operation {
+impl<V: Clone + Display> HashMap<V> {
+    /// Applies all operations, and describes the boxes after each one
+    /// like in the puzzle description.
+    #[allow(dead_code)]
+    fn trace(&mut self, operations: &[Operation<V>]) -> String {
+        operations
+            .iter()
+            .map(|operation| {
+                self.apply(operation.clone());
+                format!("After \"{operation}\":\n{self}")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl<V: Display> Display for HashMap<V> {
+    /// Shows every box that isn't empty.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (box_index, lens_box) in self.boxes.iter().enumerate() {
+            if lens_box.is_empty() {
+                continue;
+            }
+            write!(f, "Box {box_index}:")?;
+            for (label, value) in lens_box {
+                write!(f, " [{label} {value}]")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The focusing power of a lens: its box number times its slot number times its focal length,
+/// where box and slot numbers start at one.
+fn focusing_power(entry: &Entry<u32>) -> u32 {
+    (entry.box_index as u32 + 1) * (entry.slot_index as u32 + 1) * entry.value
+}
+
+/// The total focal length of all lenses, regardless of their position.
+#[allow(dead_code)]
+fn focal_length(entry: &Entry<u32>) -> u32 {
+    *entry.value
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Operation<V = u32> {
     Remove(String),
-    Insert(String, u32),
+    Insert(String, V),
 }
 
-impl FromStr for Operation {
-    type Err = ();
+impl<V: FromStr> FromStr for Operation<V> {
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Some(label) = s.strip_suffix('-') {
             Ok(Operation::Remove(label.to_string()))
-        } else if let Some((label, focal_length)) = s.split_once('=') {
-            Ok(Operation::Insert(
-                label.to_string(),
-                focal_length.parse().unwrap(),
-            ))
+        } else if let Some((label, value)) = s.split_once('=') {
+            Ok(Operation::Insert(label.to_string(), parse_number(value)?))
         } else {
-            panic!("invalid operation: {s}")
+            Err(ParseError::expected_after(s, "'-' or '='"))
+        }
+    }
+}
+
+impl<V: Display> Display for Operation<V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Remove(label) => write!(f, "{label}-"),
+            Operation::Insert(label, value) => write!(f, "{label}={value}"),
         }
     }
 }
 
 #[aoc(day15, part2)]
-fn part2(input: &[String]) -> u32 {
+fn part2(input: &[Step]) -> u32 {
     let mut hashmap = HashMap::new();
-    let operations = input
-        .iter()
-        .map(|s| Operation::from_str(s).unwrap())
-        .collect::<Vec<_>>();
-    for op in operations {
-        match op {
-            Operation::Remove(label) => hashmap.remove(&label),
-            Operation::Insert(label, focal_length) => hashmap.insert(Lens {
-                label,
-                focal_length,
-            }),
-        };
-    }
-    hashmap.power()
+    for step in input {
+        hashmap.apply(step.operation.clone());
+    }
+    hashmap.score(focusing_power)
 }
 
 #[cfg(test)]
@@ -124,11 +230,72 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 1320);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 1320);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 145);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 145);
+    }
+
+    #[test]
+    fn parse_error() {
+        let error = parse("rn=1,cm,qp=3").unwrap_err();
+        assert_eq!(
+            (error.day, error.line, error.column),
+            (Some(15), Some(1), Some(8))
+        );
+    }
+
+    fn operations() -> Vec<Operation> {
+        parse(INPUT)
+            .unwrap()
+            .into_iter()
+            .map(|step| step.operation)
+            .collect()
+    }
+
+    #[test]
+    fn trace() {
+        let mut hashmap = HashMap::new();
+        let trace = hashmap.trace(&operations()[..3]);
+        let expected = "After \"rn=1\":
+Box 0: [rn 1]
+
+After \"cm-\":
+Box 0: [rn 1]
+
+After \"qp=3\":
+Box 0: [rn 1]
+Box 1: [qp 3]
+";
+        assert_eq!(trace, expected);
+    }
+
+    #[test]
+    fn entries() {
+        let mut hashmap = HashMap::new();
+        for operation in operations() {
+            hashmap.apply(operation);
+        }
+        let labels = hashmap.iter().map(|entry| entry.label).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["rn", "cm", "ot", "ab", "pc"]);
+        assert_eq!(hashmap.get("pc"), Some(&6));
+        assert_eq!(hashmap.get("qp"), None);
+        assert_eq!(hashmap.score(focal_length), 1 + 2 + 7 + 5 + 6);
+        assert_eq!(hashmap.insert("rn".to_string(), 9), Some(1));
+        assert_eq!(hashmap.remove("rn"), Some(9));
+    }
+
+    #[test]
+    fn custom_hasher() {
+        let mut hashmap = HashMap::with_hasher(NonZeroUsize::new(2).unwrap(), |label| label.len());
+        hashmap.insert("a".to_string(), "one");
+        hashmap.insert("bb".to_string(), "two");
+        hashmap.insert("ccc".to_string(), "three");
+        assert_eq!(
+            hashmap.to_string(),
+            "Box 0: [bb two]\nBox 1: [a one] [ccc three]\n"
+        );
     }
 }
@@ -1,8 +1,9 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use aoc_runner_derive::{aoc, aoc_generator};
+use pathfinding::directed::strongly_connected_components::strongly_connected_components;
 
-use crate::util::{Direction, Vector2D};
+use crate::util::{Direction, Grid, ParseError, Vector2D};
 
 enum Piece {
     MirrorUpRight,
@@ -12,59 +13,26 @@ enum Piece {
 }
 
 struct Contraption {
-    width: i32,
-    height: i32,
-    pieces: HashMap<Vector2D, Piece>,
+    pieces: Grid<Option<Piece>>,
 }
 
 #[aoc_generator(day16)]
-fn parse(input: &str) -> Contraption {
-    let height = input.lines().count() as i32;
-    let width = input.lines().next().unwrap().len() as i32;
-    let mut pieces = HashMap::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let piece = match c {
-                '.' => None,
-                '/' => Some(Piece::MirrorUpRight),
-                '\\' => Some(Piece::MirrorDownRight),
-                '-' => Some(Piece::SplitterHorizontal),
-                '|' => Some(Piece::SplitterVertical),
-                _ => panic!("invalid piece {c}"),
-            };
-            if let Some(piece) = piece {
-                pieces.insert(Vector2D::new(x as i32, y as i32), piece);
-            }
-        }
-    }
-    Contraption {
-        width,
-        height,
-        pieces,
-    }
-}
-
-impl Direction {
-    fn mirror_upright(self) -> Direction {
-        match self {
-            Direction::N => Direction::E,
-            Direction::E => Direction::N,
-            Direction::W => Direction::S,
-            Direction::S => Direction::W,
-        }
-    }
-
-    fn mirror_downright(self) -> Direction {
-        match self {
-            Direction::N => Direction::W,
-            Direction::W => Direction::N,
-            Direction::S => Direction::E,
-            Direction::E => Direction::S,
-        }
-    }
+fn parse(input: &str) -> Result<Contraption, ParseError> {
+    let pieces = Grid::parse(input, "mirror or splitter", |c| {
+        Some(match c {
+            '.' => None,
+            '/' => Some(Piece::MirrorUpRight),
+            '\\' => Some(Piece::MirrorDownRight),
+            '-' => Some(Piece::SplitterHorizontal),
+            '|' => Some(Piece::SplitterVertical),
+            _ => return None,
+        })
+    })
+    .map_err(|err| err.locate(16, input))?;
+    Ok(Contraption { pieces })
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 struct Beam {
     pos: Vector2D,
     dir: Direction,
@@ -81,7 +49,7 @@ type Beams = HashMap<Vector2D, HashSet<Direction>>;
 impl Contraption {
     fn step(&self, beam: Beam) -> Vec<Beam> {
         let next_pos = beam.pos + beam.dir.step();
-        match (self.pieces.get(&next_pos), beam.dir) {
+        match (self.pieces.get(next_pos).and_then(Option::as_ref), beam.dir) {
             (None, _)
             | (Some(Piece::SplitterHorizontal), Direction::W | Direction::E)
             | (Some(Piece::SplitterVertical), Direction::N | Direction::S) => {
@@ -90,11 +58,11 @@ impl Contraption {
             }
             (Some(Piece::MirrorUpRight), dir) => {
                 // beam is reflected
-                vec![Beam::new(next_pos, dir.mirror_upright())]
+                vec![Beam::new(next_pos, dir.reflect_slash())]
             }
             (Some(Piece::MirrorDownRight), dir) => {
                 // beam is reflected
-                vec![Beam::new(next_pos, dir.mirror_downright())]
+                vec![Beam::new(next_pos, dir.reflect_backslash())]
             }
             (Some(Piece::SplitterHorizontal), Direction::N | Direction::S) => {
                 // beam is split
@@ -114,10 +82,12 @@ impl Contraption {
     }
 
     fn is_in_bounds(&self, pos: &Vector2D) -> bool {
-        (0..self.width).contains(&pos.x()) && (0..self.height).contains(&pos.y())
+        self.pieces.contains(*pos)
     }
 }
 
+/// Simulates a single beam one step at a time.
+#[allow(unused)]
 fn solve(input: &Contraption, start_beam: Beam) -> usize {
     let mut beams = Beams::new();
     let mut queue = VecDeque::new();
@@ -136,28 +106,199 @@ fn solve(input: &Contraption, start_beam: Beam) -> usize {
     beams.len()
 }
 
+/// A set of cells on the grid, stored as one bit per cell.
+#[derive(Debug, Clone)]
+struct CellSet {
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl CellSet {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            bits: vec![0; (width * height).div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, pos: Vector2D) {
+        let index = pos.y() as usize * self.width + pos.x() as usize;
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    fn union_with(&mut self, other: &CellSet) {
+        for (bits, other_bits) in self.bits.iter_mut().zip(&other.bits) {
+            *bits |= other_bits;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+}
+
+/// The path of a beam until it hits the next mirror or splitter.
+struct Segment {
+    /// The cells that the beam passes through, including the piece it hits.
+    cells: Vec<Vector2D>,
+    /// The beams leaving the piece it hits, or nothing if it leaves the grid.
+    next: Vec<Beam>,
+}
+
+impl Contraption {
+    fn trace(&self, beam: Beam) -> Segment {
+        let mut cells = vec![];
+        let mut pos = beam.pos;
+        loop {
+            let next_pos = pos + beam.dir.step();
+            match self.pieces.get(next_pos) {
+                None => {
+                    return Segment {
+                        cells,
+                        next: vec![],
+                    }
+                }
+                Some(None) => {
+                    cells.push(next_pos);
+                    pos = next_pos;
+                }
+                Some(Some(_)) => {
+                    cells.push(next_pos);
+                    let next = self.step(Beam::new(pos, beam.dir));
+                    return Segment { cells, next };
+                }
+            }
+        }
+    }
+
+    /// All beams that enter the grid from outside.
+    fn entry_beams(&self) -> Vec<Beam> {
+        let width = self.pieces.width() as i32;
+        let height = self.pieces.height() as i32;
+        let mut entries = vec![];
+        for x in 0..width {
+            entries.push(Beam::new(Vector2D::new(x, -1), Direction::S));
+            entries.push(Beam::new(Vector2D::new(x, height), Direction::N));
+        }
+        for y in 0..height {
+            entries.push(Beam::new(Vector2D::new(-1, y), Direction::E));
+            entries.push(Beam::new(Vector2D::new(width, y), Direction::W));
+        }
+        entries
+    }
+}
+
+/// All beams leaving a mirror or splitter, with the segments between them.
+///
+/// The beams that reach each other form strongly connected components,
+/// which energize the same cells. Each component stores the cells energized
+/// by itself and all components after it, so they only need to be computed once.
+struct BeamGraph {
+    segments: HashMap<Beam, Segment>,
+    component_of: HashMap<Beam, usize>,
+    energized: Vec<CellSet>,
+}
+
+impl BeamGraph {
+    fn new(contraption: &Contraption, entries: &[Beam]) -> Self {
+        // Find all beams leaving a piece that can be reached from any entry
+        let mut segments = HashMap::new();
+        let mut queue = entries
+            .iter()
+            .flat_map(|&entry| contraption.trace(entry).next)
+            .collect::<Vec<_>>();
+        while let Some(beam) = queue.pop() {
+            if segments.contains_key(&beam) {
+                continue;
+            }
+            let segment = contraption.trace(beam);
+            queue.extend(segment.next.iter().copied());
+            segments.insert(beam, segment);
+        }
+        // Condense the strongly connected components
+        let mut beams = segments.keys().copied().collect::<Vec<_>>();
+        beams.sort_by_key(|beam| (beam.pos.y(), beam.pos.x(), beam.dir as usize));
+        let components = strongly_connected_components(&beams, |beam| segments[beam].next.clone());
+        let component_of = components
+            .iter()
+            .enumerate()
+            .flat_map(|(index, component)| component.iter().map(move |&beam| (beam, index)))
+            .collect::<HashMap<_, _>>();
+        let mut graph = Self {
+            segments,
+            component_of,
+            energized: vec![],
+        };
+        let width = contraption.pieces.width();
+        let height = contraption.pieces.height();
+        let mut energized = vec![None; components.len()];
+        for index in 0..components.len() {
+            graph.fill_component(index, &components, &mut energized, width, height);
+        }
+        graph.energized = energized.into_iter().map(Option::unwrap).collect();
+        graph
+    }
+
+    fn fill_component(
+        &self,
+        index: usize,
+        components: &[Vec<Beam>],
+        energized: &mut [Option<CellSet>],
+        width: usize,
+        height: usize,
+    ) {
+        if energized[index].is_some() {
+            return;
+        }
+        let mut cells = CellSet::new(width, height);
+        for beam in &components[index] {
+            let segment = &self.segments[beam];
+            for &pos in &segment.cells {
+                cells.insert(pos);
+            }
+            for next in &segment.next {
+                let next_index = self.component_of[next];
+                if next_index != index {
+                    self.fill_component(next_index, components, energized, width, height);
+                    cells.union_with(energized[next_index].as_ref().unwrap());
+                }
+            }
+        }
+        energized[index] = Some(cells);
+    }
+
+    /// The number of cells energized by a beam entering the grid.
+    fn energized(&self, contraption: &Contraption, entry: Beam) -> usize {
+        let segment = contraption.trace(entry);
+        let mut cells = CellSet::new(contraption.pieces.width(), contraption.pieces.height());
+        for &pos in &segment.cells {
+            cells.insert(pos);
+        }
+        for next in &segment.next {
+            cells.union_with(&self.energized[self.component_of[next]]);
+        }
+        cells.len()
+    }
+}
+
 #[aoc(day16, part1)]
 fn part1(input: &Contraption) -> usize {
     let start_beam = Beam::new(Vector2D::new(-1, 0), Direction::E);
-    solve(input, start_beam)
+    BeamGraph::new(input, &[start_beam]).energized(input, start_beam)
 }
 
 #[aoc(day16, part2)]
 fn part2(input: &Contraption) -> usize {
-    let mut max_energized = 0;
-    for x in 0..input.width {
-        let from_top = Beam::new(Vector2D::new(x, -1), Direction::S);
-        let from_bottom = Beam::new(Vector2D::new(x, input.height), Direction::N);
-        max_energized = max_energized.max(solve(input, from_top));
-        max_energized = max_energized.max(solve(input, from_bottom));
-    }
-    for y in 0..input.height {
-        let from_left = Beam::new(Vector2D::new(-1, y), Direction::E);
-        let from_right = Beam::new(Vector2D::new(input.width, y), Direction::W);
-        max_energized = max_energized.max(solve(input, from_left));
-        max_energized = max_energized.max(solve(input, from_right));
-    }
-    max_energized
+    let entries = input.entry_beams();
+    let graph = BeamGraph::new(input, &entries);
+    entries
+        .into_iter()
+        .map(|entry| graph.energized(input, entry))
+        .max()
+        .unwrap()
 }
 
 #[cfg(test)]
@@ -177,11 +318,25 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 46);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 46);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 51);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 51);
+    }
+
+    #[test]
+    fn beam_graph() {
+        let contraption = parse(INPUT).unwrap();
+        let entries = contraption.entry_beams();
+        let graph = BeamGraph::new(&contraption, &entries);
+        for entry in entries {
+            assert_eq!(
+                graph.energized(&contraption, entry),
+                solve(&contraption, entry),
+                "{entry:?}"
+            );
+        }
     }
 }
@@ -1,31 +1,41 @@
-use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 use aoc_runner_derive::{aoc, aoc_generator};
-use pathfinding::directed::dijkstra::dijkstra;
+use pathfinding::directed::astar::astar;
 
-use crate::util::{Direction, Vector2D};
+use crate::util::{Direction, Grid, ParseError, Vector2D};
 
-struct Map {
-    width: i32,
-    height: i32,
-    blocks: HashMap<Vector2D, u32>,
-}
+type Map = Grid<u32>;
 
 #[aoc_generator(day17)]
-fn parse(input: &str) -> Map {
-    let height = input.lines().count() as i32;
-    let width = input.lines().next().unwrap().len() as i32;
-    let mut blocks = HashMap::new();
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            blocks.insert(Vector2D::new(x as i32, y as i32), c.to_digit(10).unwrap());
-        }
-    }
-    Map {
-        width,
-        height,
-        blocks,
-    }
+fn parse(input: &str) -> Result<Map, ParseError> {
+    Grid::parse(input, "digit", |c| c.to_digit(10)).map_err(|err| err.locate(17, input))
+}
+
+/// The movement rules for a crucible.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct CrucibleRules {
+    /// The minimum number of steps in a straight line, before turning or stopping.
+    min_straight: usize,
+    /// The maximum number of steps in a straight line, before it must turn.
+    max_straight: usize,
+    /// Whether the crucible can turn around and go back the way it came.
+    allow_reverse: bool,
+}
+
+impl CrucibleRules {
+    const NORMAL: Self = Self {
+        min_straight: 1,
+        max_straight: 3,
+        allow_reverse: false,
+    };
+
+    const ULTRA: Self = Self {
+        min_straight: 4,
+        max_straight: 10,
+        allow_reverse: false,
+    };
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -36,28 +46,23 @@ struct State {
 }
 
 impl State {
-    fn successors(&self, map: &Map, part2: bool) -> Vec<(State, u32)> {
+    fn successors(&self, map: &Map, rules: &CrucibleRules) -> Vec<(State, u32)> {
         Direction::all()
             .into_iter()
             .filter_map(|dir| {
                 let pos = self.pos + dir.step();
-                let cost = *map.blocks.get(&pos)?;
-                // Cannot go backwards (but ignore at start)
-                if self.straight != 0 && dir == self.dir.opposite() {
-                    return None;
-                }
-                if part2 {
-                    // Must NOT turn until at least 4 straight steps (but ignore at start)
-                    if dir != self.dir && self.straight < 4 && self.straight != 0 {
+                let cost = *map.get(pos)?;
+                // At the start, the crucible can go in any direction
+                if self.straight != 0 {
+                    if !rules.allow_reverse && dir == self.dir.opposite() {
                         return None;
                     }
-                    // Must turn after 10 straight steps
-                    if dir == self.dir && self.straight >= 10 {
+                    // Must go straight for a while before turning
+                    if dir != self.dir && self.straight < rules.min_straight {
                         return None;
                     }
-                } else {
-                    // Must turn after 3 straight steps
-                    if dir == self.dir && self.straight >= 3 {
+                    // Must turn after going straight for too long
+                    if dir == self.dir && self.straight >= rules.max_straight {
                         return None;
                     }
                 }
@@ -72,36 +77,78 @@ impl State {
     }
 }
 
-fn solve(map: &Map, part2: bool) -> u32 {
+/// The path with the least heat loss.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct CruciblePath {
+    heat_loss: u32,
+    start: Vector2D,
+    /// Every position along the path after the start, with the direction used to get there.
+    steps: Vec<(Vector2D, Direction)>,
+}
+
+impl CruciblePath {
+    /// Draws the path over the map, using arrows like in the puzzle description.
+    #[allow(dead_code)]
+    fn render(&self, map: &Map) -> String {
+        let mut grid = map.map(|&heat_loss| char::from_digit(heat_loss, 10).unwrap_or('?'));
+        for &(pos, dir) in &self.steps {
+            grid[pos] = dir.arrow();
+        }
+        grid.to_string()
+    }
+}
+
+fn solve(map: &Map, rules: &CrucibleRules) -> Option<CruciblePath> {
     let start = State {
         pos: Vector2D::new(0, 0),
         dir: Direction::N,
         straight: 0,
     };
-    let goal = Vector2D::new(map.width - 1, map.height - 1);
-    let (_path, cost) = dijkstra(
+    let goal = Vector2D::new(map.width() as i32 - 1, map.height() as i32 - 1);
+    // Every step loses at least this much heat, which may be nothing
+    let min_heat_loss = map
+        .iter()
+        .map(|(_, &heat_loss)| heat_loss)
+        .min()
+        .unwrap_or(0);
+    let (path, heat_loss) = astar(
         &start,
-        |state| state.successors(map, part2),
-        |state| {
-            // Cannot stop at end unless at least 4 straight steps
-            if part2 && state.straight < 4 {
-                return false;
-            }
-            state.pos == goal
-        },
-    )
-    .unwrap();
-    cost
+        |state| state.successors(map, rules),
+        |state| (goal - state.pos).manhattan_distance() as u32 * min_heat_loss,
+        // Cannot stop without going straight for long enough
+        |state| state.pos == goal && state.straight >= rules.min_straight,
+    )?;
+    Some(CruciblePath {
+        heat_loss,
+        start: start.pos,
+        steps: path[1..]
+            .iter()
+            .map(|state| (state.pos, state.dir))
+            .collect(),
+    })
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct NoPath;
+
+impl Display for NoPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the crucible cannot reach the factory")
+    }
 }
 
+impl Error for NoPath {}
+
 #[aoc(day17, part1)]
-fn part1(map: &Map) -> u32 {
-    solve(map, false)
+fn part1(map: &Map) -> Result<u32, NoPath> {
+    let path = solve(map, &CrucibleRules::NORMAL).ok_or(NoPath)?;
+    Ok(path.heat_loss)
 }
 
 #[aoc(day17, part2)]
-fn part2(map: &Map) -> u32 {
-    solve(map, true)
+fn part2(map: &Map) -> Result<u32, NoPath> {
+    let path = solve(map, &CrucibleRules::ULTRA).ok_or(NoPath)?;
+    Ok(path.heat_loss)
 }
 
 #[cfg(test)]
@@ -124,7 +171,7 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 102);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(102));
     }
 
     const INPUT2: &str = "111111111111
@@ -135,11 +182,75 @@ mod tests {
 
     #[test]
     fn part2_example1() {
-        assert_eq!(part2(&parse(INPUT)), 94);
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(94));
     }
 
     #[test]
     fn part2_example2() {
-        assert_eq!(part2(&parse(INPUT2)), 71);
+        assert_eq!(part2(&parse(INPUT2).unwrap()), Ok(71));
+    }
+
+    #[test]
+    fn part2_no_path() {
+        // The ultra crucible cannot go straight for long enough to stop
+        assert_eq!(part2(&parse("12\n34").unwrap()), Err(NoPath));
+    }
+
+    #[test]
+    fn render_path() {
+        let path = solve(&parse(INPUT).unwrap(), &CrucibleRules::NORMAL).unwrap();
+        assert_eq!(path.heat_loss, 102);
+        assert_eq!(path.start, Vector2D::new(0, 0));
+        assert_eq!(path.steps.last().unwrap().0, Vector2D::new(12, 12));
+        // Same heat loss as the path in the puzzle description, but a different route
+        let expected = "2>>>43^>>>323
+321v>>>53v623
+325524565v>54
+3446585845v52
+4546657867v>6
+14385987984v4
+44578769877v6
+36378779796v>
+465496798688v
+456467998645v
+12246868655<v
+25465488877v5
+43226746555v>";
+        assert_eq!(path.render(&parse(INPUT).unwrap()), expected);
+    }
+
+    #[test]
+    fn zero_heat_loss() {
+        let map = parse(
+            "00790561
+05040831
+00011678
+07018350
+00050170
+02151290
+03780020
+02256320",
+        )
+        .unwrap();
+        assert_eq!(part1(&map), Ok(8));
+    }
+
+    #[test]
+    fn reverse_rules() {
+        // In a narrow corridor, the only way to avoid going straight too long is to turn around
+        let map = parse("1\n1\n1\n1\n1").unwrap();
+        let rules = CrucibleRules {
+            min_straight: 1,
+            max_straight: 2,
+            allow_reverse: true,
+        };
+        let path = solve(&map, &rules).unwrap();
+        assert_eq!(path.heat_loss, 8);
+        assert_eq!(path.render(&map), "1\n^\n^\nv\nv");
+        let rules = CrucibleRules {
+            allow_reverse: false,
+            ..rules
+        };
+        assert_eq!(solve(&map, &rules), None);
     }
 }
@@ -2,7 +2,10 @@ use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::{array_windows, Direction, Vector2D};
+use crate::util::{
+    parse_lines, parse_number, split_once, strip_prefix, strip_suffix, Direction, ParseError,
+    Polygon, Vector2D,
+};
 
 #[derive(Debug, Clone)]
 struct Instruction {
@@ -12,66 +15,54 @@ struct Instruction {
 }
 
 impl FromStr for Instruction {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (dir, s) = s.split_once(' ').unwrap();
-        let (meters, color) = s.split_once(' ').unwrap();
-        let dir = match dir {
-            "U" => Direction::N,
-            "D" => Direction::S,
-            "L" => Direction::W,
-            "R" => Direction::E,
-            _ => panic!("invalid direction {dir}"),
-        };
-        let meters = meters.parse().unwrap();
-        let color = color
-            .strip_prefix("(#")
-            .unwrap()
-            .strip_suffix(')')
-            .unwrap()
-            .to_string();
-        Ok(Instruction { dir, meters, color })
+        let (dir, rest) = split_once(s, " ")?;
+        let (meters, color) = split_once(rest, " ")?;
+        let mut chars = dir.chars();
+        let dir = match (chars.next(), chars.next()) {
+            (Some(c), None) => Direction::from_udlr(c),
+            _ => None,
+        }
+        .ok_or_else(|| ParseError::expected(dir, "U, D, L or R"))?;
+        let meters = parse_number(meters)?;
+        let color = strip_suffix(strip_prefix(color, "(#")?, ")")?;
+        if let Some(i) = color.find(|c: char| !c.is_ascii_hexdigit()) {
+            return Err(ParseError::expected(&color[i..], "hex digit"));
+        }
+        if color.len() != 6 {
+            return Err(ParseError::expected(color, "six hex digits"));
+        }
+        if !matches!(&color[5..], "0" | "1" | "2" | "3") {
+            return Err(ParseError::expected(
+                &color[5..],
+                "hex direction from 0 to 3",
+            ));
+        }
+        Ok(Instruction {
+            dir,
+            meters,
+            color: color.to_string(),
+        })
     }
 }
 
 #[aoc_generator(day18)]
-fn parse(input: &str) -> Vec<Instruction> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    parse_lines(input).map_err(|err| err.locate(18, input))
 }
 
 #[aoc(day18, part1)]
 fn part1(plan: &[Instruction]) -> u64 {
-    let mut edge = Vec::<Vector2D>::new();
     // Dig out the edge
-    let mut pos = Vector2D::new(0, 0);
-    edge.push(pos);
-    for instruction in plan {
-        pos += instruction.dir.step() * instruction.meters;
-        edge.push(pos);
-    }
-    // Dig out the interior
-    trench_area(&edge)
-}
-
-fn trench_area(edge: &[Vector2D]) -> u64 {
-    // https://en.wikipedia.org/wiki/Shoelace_formula#Trapezoid_formula
-    assert_eq!(edge.first(), edge.last());
-    let mut area = 0i64;
-    let mut perimeter = 0i64;
-    for &[pos, next_pos] in array_windows(edge) {
-        area += (pos.y() + next_pos.y()) as i64 * (pos.x() - next_pos.x()) as i64;
-        perimeter += (next_pos - pos).manhattan_distance() as i64;
-    }
-    area = area.abs() / 2;
-    // https://en.wikipedia.org/wiki/Pick%27s_theorem
-    // A = I + B/2 - 1
-    // area = A and perimeter = B, but we actually need I + B
-    // I = A - B/2 + 1
-    // I + B = A + B/2 + 1
-    // https://www.reddit.com/r/adventofcode/comments/18lg2we/comment/kdxd7yg/?utm_source=share&utm_medium=web2x&context=3
-    area += perimeter / 2 + 1;
-    area.unsigned_abs()
+    let edge = Polygon::<i64>::from_steps(
+        Vector2D::zero(),
+        plan.iter()
+            .map(|instruction| (instruction.dir, instruction.meters.into())),
+    );
+    // Dig out the interior, and count both the interior and the edge
+    edge.lattice_points().unsigned_abs()
 }
 
 fn fix_instruction(instruction: &Instruction) -> Instruction {
@@ -118,11 +109,19 @@ U 2 (#7a21e3)";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 62);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 62);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 952_408_144_115);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 952_408_144_115);
+    }
+
+    #[test]
+    fn parse_direction() {
+        for line in ["N 6 (#70c710)", "> 6 (#70c710)", "RR 6 (#70c710)"] {
+            let error = line.parse::<Instruction>().unwrap_err();
+            assert!(error.to_string().contains("U, D, L or R"), "{error}");
+        }
     }
 }
@@ -1,8 +1,15 @@
-use std::collections::HashMap;
+use std::array;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{
+    parse_lines, parse_number, split_once, strip_prefix, strip_suffix, Cuboid, ParseError, Vector,
+};
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Op {
     Greater,
@@ -45,49 +52,57 @@ struct Part {
 
 #[derive(Debug, Clone)]
 struct Input {
+    #[allow(dead_code)]
     workflows: HashMap<String, Workflow>,
     parts: Vec<Part>,
+    /// The workflows compiled once for both parts of the puzzle.
+    compiled: Result<CompiledWorkflows, WorkflowError>,
 }
 
-impl From<char> for RatingId {
-    fn from(value: char) -> Self {
-        match value {
-            'x' => RatingId::X,
-            'm' => RatingId::M,
-            'a' => RatingId::A,
-            's' => RatingId::S,
-            _ => panic!("invalid rating ID: {value}"),
-        }
+impl FromStr for RatingId {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "x" => RatingId::X,
+            "m" => RatingId::M,
+            "a" => RatingId::A,
+            "s" => RatingId::S,
+            _ => return Err(ParseError::expected(s, "x, m, a or s")),
+        })
     }
 }
 
 impl FromStr for Destination {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "A" => Destination::Accept,
             "R" => Destination::Reject,
+            "" => return Err(ParseError::expected(s, "workflow name")),
             worfklow => Destination::Workflow(worfklow.to_string()),
         })
     }
 }
 
 impl FromStr for Rule {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (rating, s) = s.split_at(1);
-        let (op, s) = s.split_at(1);
-        let (value, dest) = s.split_once(':').unwrap();
-        let rating = rating.chars().next().unwrap().into();
+        let op_index = s
+            .find(['<', '>'])
+            .ok_or_else(|| ParseError::expected_after(s, "'<' or '>'"))?;
+        let (rating, rest) = s.split_at(op_index);
+        let (op, rest) = rest.split_at(1);
+        let (value, dest) = split_once(rest, ":")?;
+        let rating = rating.parse()?;
         let op = match op {
             ">" => Op::Greater,
-            "<" => Op::Less,
-            _ => panic!("invalid op {op}"),
+            _ => Op::Less,
         };
-        let value = value.parse().unwrap();
-        let dest = dest.parse().unwrap();
+        let value = parse_number(value)?;
+        let dest = dest.parse()?;
         Ok(Rule {
             rating,
             op,
@@ -98,199 +113,466 @@ impl FromStr for Rule {
 }
 
 impl FromStr for Workflow {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let rules = s.split(',').collect::<Vec<_>>();
-        let (default, rules) = rules.split_last().unwrap();
-        let rules = rules.iter().map(|s| s.parse().unwrap()).collect();
-        let default = default.parse().unwrap();
+        let (default, rules) = rules
+            .split_last()
+            .ok_or_else(|| ParseError::expected(s, "rules"))?;
+        let rules = rules.iter().map(|s| s.parse()).collect::<Result<_, _>>()?;
+        let default = default.parse()?;
         Ok(Workflow { rules, default })
     }
 }
 
 impl FromStr for Part {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.strip_prefix('{').unwrap();
-        let s = s.strip_suffix('}').unwrap();
-        let ratings = s
-            .split(',')
-            .map(|s| {
-                let (rating, value) = s.split_once('=').unwrap();
-                assert_eq!(rating.len(), 1);
-                let _rating: RatingId = rating.chars().next().unwrap().into();
-
-                value.parse().unwrap()
-            })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
-        Ok(Part { ratings })
+        let inner = strip_suffix(strip_prefix(s, "{")?, "}")?;
+        let mut ratings = [None; 4];
+        for rating in inner.split(',') {
+            let (rating, value) = split_once(rating, "=")?;
+            let rating: RatingId = rating.parse()?;
+            ratings[rating as usize] = Some(parse_number(value)?);
+        }
+        let ratings = ratings
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ParseError::expected_after(inner, "ratings for x, m, a and s"))?;
+        Ok(Part {
+            ratings: ratings.try_into().unwrap(),
+        })
     }
 }
 
 #[aoc_generator(day19)]
-fn parse(input: &str) -> Input {
-    let (workflows, parts) = input.split_once("\n\n").unwrap();
+fn parse(input: &str) -> Result<Input, ParseError> {
+    parse_input(input).map_err(|err| err.locate(19, input))
+}
+
+fn parse_input(input: &str) -> Result<Input, ParseError> {
+    let (workflows, parts) = split_once(input, "\n\n")?;
     let workflows = workflows
         .lines()
         .map(|s| {
-            let (name, s) = s.split_once('{').unwrap();
-            let workflow = s.strip_suffix('}').unwrap().parse().unwrap();
-            (name.to_string(), workflow)
+            let (name, rest) = split_once(s, "{")?;
+            let workflow = strip_suffix(rest, "}")?.parse()?;
+            Ok((name.to_string(), workflow))
         })
-        .collect();
-    let parts = parts.lines().map(|s| s.parse().unwrap()).collect();
-    Input { workflows, parts }
+        .collect::<Result<_, ParseError>>()?;
+    let parts = parse_lines(parts)?;
+    let compiled = CompiledWorkflows::with_range(&workflows, bounding_range(&parts));
+    Ok(Input {
+        workflows,
+        parts,
+        compiled,
+    })
 }
 
-impl Rule {
-    fn matches(&self, part: &Part) -> bool {
-        let rating = part.ratings[self.rating as usize];
-        match self.op {
-            Op::Greater => rating > self.value,
-            Op::Less => rating < self.value,
+impl Display for Destination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Destination::Workflow(name) => write!(f, "{name}"),
+            Destination::Accept => write!(f, "A"),
+            Destination::Reject => write!(f, "R"),
         }
     }
 }
 
-fn process(part: &Part, workflows: &HashMap<String, Workflow>) -> bool {
-    let mut workflow = workflows.get("in").unwrap();
-    loop {
-        let dest = workflow
-            .rules
-            .iter()
-            .find_map(|rule| rule.matches(part).then_some(&rule.dest))
-            .unwrap_or(&workflow.default);
-        match dest {
-            Destination::Accept => return true,
-            Destination::Reject => return false,
-            Destination::Workflow(name) => {
-                workflow = workflows.get(name).unwrap();
-            }
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rating = ["x", "m", "a", "s"][self.rating as usize];
+        let op = match self.op {
+            Op::Greater => '>',
+            Op::Less => '<',
+        };
+        write!(f, "{rating}{op}{}:{}", self.value, self.dest)
+    }
+}
+
+impl Display for Workflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for rule in &self.rules {
+            write!(f, "{rule},")?;
         }
+        write!(f, "{}", self.default)
     }
 }
 
-#[aoc(day19, part1)]
-fn part1(input: &Input) -> i64 {
-    input
-        .parts
-        .iter()
-        .filter(|part| process(part, &input.workflows))
-        .map(|part| part.ratings.iter().sum::<i64>())
-        .sum()
+/// Formats workflows in the input syntax, starting with `in` and then sorted by name.
+#[allow(dead_code)]
+fn format_workflows(workflows: &HashMap<String, Workflow>) -> String {
+    let mut names = workflows.keys().collect::<Vec<_>>();
+    names.sort_by_key(|&name| (name != START, name));
+    names
+        .into_iter()
+        .map(|name| format!("{name}{{{}}}", workflows[name]))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-#[derive(Debug, Clone)]
-struct PartRange {
-    ratings: [(i64, i64); 4],
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum WorkflowError {
+    /// A destination refers to a workflow that doesn't exist.
+    Missing(String),
+    /// Workflows that send parts to each other in a loop, starting and ending with the same one.
+    Cycle(Vec<String>),
 }
 
-impl PartRange {
-    fn all() -> Self {
-        Self {
-            ratings: [(1, 4000); 4],
+impl Display for WorkflowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkflowError::Missing(name) => write!(f, "workflow {name} does not exist"),
+            WorkflowError::Cycle(names) => {
+                write!(f, "workflows form a cycle: {}", names.join(" -> "))
+            }
         }
     }
+}
 
-    fn is_empty(&self) -> bool {
-        self.ratings.iter().any(|(start, end)| start > end)
+impl Error for WorkflowError {}
+
+/// The workflow where every part starts.
+const START: &str = "in";
+
+impl Workflow {
+    fn destinations(&self) -> impl Iterator<Item = &Destination> {
+        self.rules
+            .iter()
+            .map(|rule| &rule.dest)
+            .chain([&self.default])
     }
 
-    /// Split into two halves.
-    /// The left half contains everything up to (but not including) split_value.
-    /// The right half contains everything starting from (and including) split_value.
-    fn split(&self, split_id: RatingId, split_value: i64) -> [Self; 2] {
-        let mut left_ratings = self.ratings;
-        let mut right_ratings = self.ratings;
-        let (_, left_end) = &mut left_ratings[split_id as usize];
-        let (right_start, _) = &mut right_ratings[split_id as usize];
-        *left_end = (*left_end).min(split_value - 1);
-        *right_start = (*right_start).max(split_value);
-        [
-            Self {
-                ratings: left_ratings,
-            },
-            Self {
-                ratings: right_ratings,
-            },
-        ]
+    fn next_workflows(&self) -> impl Iterator<Item = &str> {
+        self.destinations().filter_map(|dest| match dest {
+            Destination::Workflow(name) => Some(name.as_str()),
+            _ => None,
+        })
     }
+}
 
-    fn size(&self) -> i64 {
-        if self.is_empty() {
-            return 0;
-        }
-        self.ratings
-            .iter()
-            .map(|&(start, end)| end - start + 1)
-            .product()
+/// Checks that the start workflow and all workflows reachable from it exist,
+/// and that no part can be sent around in circles.
+/// Workflows that no part can reach are not checked.
+fn check_workflows(workflows: &HashMap<String, Workflow>) -> Result<(), WorkflowError> {
+    check_reachable(START, workflows, &mut vec![], &mut HashSet::new())
+}
+
+/// Depth-first search from `name`, looking for missing workflows
+/// and for a workflow that is already on the current path.
+fn check_reachable<'a>(
+    name: &'a str,
+    workflows: &'a HashMap<String, Workflow>,
+    path: &mut Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+) -> Result<(), WorkflowError> {
+    if let Some(index) = path.iter().position(|&other| other == name) {
+        let cycle = path[index..].iter().chain([&name]);
+        return Err(WorkflowError::Cycle(
+            cycle.map(|name| name.to_string()).collect(),
+        ));
+    }
+    if !done.insert(name) {
+        return Ok(());
     }
+    let workflow = workflows
+        .get(name)
+        .ok_or_else(|| WorkflowError::Missing(name.to_string()))?;
+    path.push(name);
+    for next in workflow.next_workflows() {
+        check_reachable(next, workflows, path, done)?;
+    }
+    path.pop();
+    Ok(())
+}
+
+/// The ranges of x, m, a and s ratings of a group of parts.
+type PartRange = Cuboid<4, i64>;
+
+fn all_parts() -> PartRange {
+    Cuboid::from_inclusive(Vector::from([1; 4]), Vector::from([4000; 4]))
 }
 
-fn process_range(
+/// The smallest range that contains all parts with ratings from 1 to 4000,
+/// as well as the given parts, whatever their ratings.
+fn bounding_range(parts: &[Part]) -> PartRange {
+    let bound = |initial: i64, f: fn(i64, i64) -> i64| {
+        Vector::from(array::from_fn(|i| {
+            parts.iter().map(|part| part.ratings[i]).fold(initial, f)
+        }))
+    };
+    Cuboid::from_inclusive(bound(1, i64::min), bound(4000, i64::max))
+}
+
+/// Where a part goes next in a compiled decision tree.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Decision {
+    Accept,
+    Reject,
+    /// Index of the split node that decides next.
+    Split(usize),
+}
+
+/// A node of a compiled decision tree, which compares one rating of a part against a value.
+#[derive(Debug, Clone)]
+struct SplitNode {
+    rating: usize,
+    value: i64,
+    /// Where parts with a lower rating go.
+    below: Decision,
+    /// Where parts with this rating or higher go.
+    at_or_above: Decision,
+}
+
+/// The workflows, reduced to a decision tree and to the disjoint ranges of parts that they accept.
+#[derive(Debug, Clone)]
+struct CompiledWorkflows {
+    /// The range of parts that the workflows were compiled for.
     range: PartRange,
-    rules: &[Rule],
-    default: &Destination,
-    workflows: &HashMap<String, Workflow>,
-) -> Vec<PartRange> {
-    if range.is_empty() {
-        return vec![];
-    }
-    if let Some((rule, rules)) = rules.split_first() {
-        // Split into two halves: one that matches, and one that doesn't
-        let (matching, failing) = match rule.op {
+    root: Decision,
+    nodes: Vec<SplitNode>,
+    accepted: Vec<PartRange>,
+    /// The rules that never match any part, as workflow name and rule index.
+    /// An index equal to the number of rules refers to the default destination.
+    #[allow(dead_code)]
+    unused: Vec<(String, usize)>,
+}
+
+impl Rule {
+    /// Splits a range into the parts that match this rule, and the parts that don't.
+    fn split(&self, range: &PartRange) -> (PartRange, PartRange) {
+        match self.op {
             Op::Greater => {
-                let [less_or_equal, greater] = range.split(rule.rating, rule.value + 1);
+                let [less_or_equal, greater] = range.split(self.rating as usize, self.value + 1);
                 (greater, less_or_equal)
             }
             Op::Less => {
-                let [less, greater_or_equal] = range.split(rule.rating, rule.value);
+                let [less, greater_or_equal] = range.split(self.rating as usize, self.value);
                 (less, greater_or_equal)
             }
+        }
+    }
+}
+
+struct Compiler<'a> {
+    workflows: &'a HashMap<String, Workflow>,
+    /// The rules that matched some parts, as workflow name and rule index.
+    used: HashSet<(&'a str, usize)>,
+    nodes: Vec<SplitNode>,
+    accepted: Vec<PartRange>,
+}
+
+impl<'a> Compiler<'a> {
+    /// Sends a non-empty range through the rules of a workflow, starting at rule `index`.
+    fn process_rules(&mut self, range: PartRange, name: &'a str, index: usize) -> Decision {
+        let workflow = &self.workflows[name];
+        let Some(rule) = workflow.rules.get(index) else {
+            self.used.insert((name, index));
+            return self.process_destination(range, &workflow.default);
+        };
+        let (matching, failing) = rule.split(&range);
+        if matching.is_empty() {
+            return self.process_rules(failing, name, index + 1);
+        }
+        self.used.insert((name, index));
+        let matched = self.process_destination(matching, &rule.dest);
+        if failing.is_empty() {
+            return matched;
+        }
+        let failed = self.process_rules(failing, name, index + 1);
+        let node = match rule.op {
+            Op::Greater => SplitNode {
+                rating: rule.rating as usize,
+                value: rule.value + 1,
+                below: failed,
+                at_or_above: matched,
+            },
+            Op::Less => SplitNode {
+                rating: rule.rating as usize,
+                value: rule.value,
+                below: matched,
+                at_or_above: failed,
+            },
         };
-        // The matching part goes to the rule's destination
-        let matching_accepted = process_destination(matching, &rule.dest, workflows);
-        // The failing part goes to the next rule
-        let failing_accepted = process_range(failing, rules, default, workflows);
-        // Combine accepted ranges from both paths
-        let mut accepted = matching_accepted;
-        accepted.extend(failing_accepted);
-        accepted
-    } else {
-        // No more rules, follow default destination
-        process_destination(range, default, workflows)
+        self.nodes.push(node);
+        Decision::Split(self.nodes.len() - 1)
+    }
+
+    fn process_destination(&mut self, range: PartRange, dest: &'a Destination) -> Decision {
+        match dest {
+            Destination::Accept => {
+                self.accepted.push(range);
+                Decision::Accept
+            }
+            Destination::Reject => Decision::Reject,
+            Destination::Workflow(name) => self.process_rules(range, name, 0),
+        }
     }
 }
 
-fn process_destination(
-    range: PartRange,
-    dest: &Destination,
-    workflows: &HashMap<String, Workflow>,
-) -> Vec<PartRange> {
-    match dest {
-        Destination::Accept => vec![range],
-        Destination::Reject => vec![],
-        Destination::Workflow(name) => {
-            let workflow = workflows.get(name).unwrap();
-            process_range(range, &workflow.rules, &workflow.default, workflows)
+impl CompiledWorkflows {
+    #[allow(dead_code)]
+    fn new(workflows: &HashMap<String, Workflow>) -> Result<Self, WorkflowError> {
+        Self::with_range(workflows, all_parts())
+    }
+
+    /// Compiles the workflows for the parts within `range` only.
+    fn with_range(
+        workflows: &HashMap<String, Workflow>,
+        range: PartRange,
+    ) -> Result<Self, WorkflowError> {
+        check_workflows(workflows)?;
+        let mut compiler = Compiler {
+            workflows,
+            used: HashSet::new(),
+            nodes: vec![],
+            accepted: vec![],
+        };
+        let root = if range.is_empty() {
+            Decision::Reject
+        } else {
+            compiler.process_rules(range, START, 0)
+        };
+        let mut unused = workflows
+            .iter()
+            .flat_map(|(name, workflow)| {
+                let used = &compiler.used;
+                (0..=workflow.rules.len())
+                    .filter(move |&index| !used.contains(&(name.as_str(), index)))
+                    .map(move |index| (name.clone(), index))
+            })
+            .collect::<Vec<_>>();
+        unused.sort();
+        Ok(Self {
+            range,
+            root,
+            nodes: compiler.nodes,
+            accepted: compiler.accepted,
+            unused,
+        })
+    }
+
+    /// Checks whether a part is accepted. Parts outside of the compiled range are never accepted.
+    fn accepts(&self, part: &Part) -> bool {
+        if !self.range.contains(Vector::from(part.ratings)) {
+            return false;
         }
+        let mut decision = self.root;
+        loop {
+            match decision {
+                Decision::Accept => return true,
+                Decision::Reject => return false,
+                Decision::Split(index) => {
+                    let node = &self.nodes[index];
+                    decision = if part.ratings[node.rating] < node.value {
+                        node.below
+                    } else {
+                        node.at_or_above
+                    };
+                }
+            }
+        }
+    }
+
+    /// The number of accepted parts within `range`.
+    fn accepted_count(&self, range: &PartRange) -> i64 {
+        self.accepted
+            .iter()
+            .filter_map(|accepted| accepted.intersection(range))
+            .map(|accepted| accepted.volume())
+            .sum()
     }
+
+    /// Rewrites the workflows to accept the same parts with fewer rules:
+    /// unused rules and unreachable workflows are removed, rules that go to the same place
+    /// as the default are dropped, and workflows without rules are inlined.
+    #[allow(dead_code)]
+    fn simplify(&self, workflows: &HashMap<String, Workflow>) -> HashMap<String, Workflow> {
+        let mut workflows = workflows.clone();
+        for (name, workflow) in workflows.iter_mut() {
+            let is_used = |index: &usize| !self.unused.contains(&(name.clone(), *index));
+            let rule_count = workflow.rules.len();
+            if !is_used(&rule_count) {
+                // Every part that reaches the last used rule matches it, so it becomes the default
+                let last = (0..rule_count).rev().find(is_used);
+                if let Some(last) = last {
+                    workflow.default = workflow.rules[last].dest.clone();
+                    workflow.rules.truncate(last);
+                }
+            }
+            let mut index = 0;
+            workflow.rules.retain(|_| {
+                index += 1;
+                is_used(&(index - 1))
+            });
+        }
+        loop {
+            for workflow in workflows.values_mut() {
+                while workflow
+                    .rules
+                    .last()
+                    .is_some_and(|rule| rule.dest == workflow.default)
+                {
+                    workflow.rules.pop();
+                }
+            }
+            // Replace references to a workflow without rules by its default destination
+            let mut names = workflows.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            let Some(inlined) = names.into_iter().find(|name| {
+                name != START
+                    && workflows[name].rules.is_empty()
+                    && workflows[name].default != Destination::Workflow(name.clone())
+            }) else {
+                break;
+            };
+            let replacement = workflows.remove(&inlined).unwrap().default;
+            let inlined = Destination::Workflow(inlined);
+            for workflow in workflows.values_mut() {
+                for dest in workflow
+                    .rules
+                    .iter_mut()
+                    .map(|rule| &mut rule.dest)
+                    .chain([&mut workflow.default])
+                {
+                    if *dest == inlined {
+                        *dest = replacement.clone();
+                    }
+                }
+            }
+        }
+        // Remove workflows that can no longer be reached
+        let mut reachable = HashSet::from([START.to_string()]);
+        let mut queue = vec![START.to_string()];
+        while let Some(name) = queue.pop() {
+            for next in workflows[&name].next_workflows() {
+                if reachable.insert(next.to_string()) {
+                    queue.push(next.to_string());
+                }
+            }
+        }
+        workflows.retain(|name, _| reachable.contains(name));
+        workflows
+    }
+}
+
+#[aoc(day19, part1)]
+fn part1(input: &Input) -> Result<i64, WorkflowError> {
+    let compiled = input.compiled.as_ref().map_err(Clone::clone)?;
+    Ok(input
+        .parts
+        .iter()
+        .filter(|part| compiled.accepts(part))
+        .map(|part| part.ratings.iter().sum::<i64>())
+        .sum())
 }
 
 #[aoc(day19, part2)]
-fn part2(input: &Input) -> i64 {
-    let in_rule = input.workflows.get("in").unwrap();
-    let accepted = process_range(
-        PartRange::all(),
-        &in_rule.rules,
-        &in_rule.default,
-        &input.workflows,
-    );
-    accepted.iter().map(|range| range.size()).sum()
+fn part2(input: &Input) -> Result<i64, WorkflowError> {
+    let compiled = input.compiled.as_ref().map_err(Clone::clone)?;
+    Ok(compiled.accepted_count(&all_parts()))
 }
 
 #[cfg(test)]
@@ -317,11 +599,74 @@ hdj{m>838:A,pv}
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 19114);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(19114));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 167_409_079_868_000);
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(167_409_079_868_000));
+    }
+
+    #[test]
+    fn part1_large_ratings() {
+        let input = parse("in{x>10:A,R}\n\n{x=5000,m=1,a=1,s=1}\n{x=-3,m=1,a=1,s=1}").unwrap();
+        assert_eq!(part1(&input), Ok(5003));
+    }
+
+    #[test]
+    fn simplify_example() {
+        let input = parse(INPUT).unwrap();
+        let compiled = CompiledWorkflows::new(&input.workflows).unwrap();
+        let simplified = compiled.simplify(&input.workflows);
+        let expected = "in{s<1351:px,qqz}
+crn{x>2662:A,R}
+hdj{m>838:A,pv}
+pv{a>1716:R,A}
+px{a<2006:qkq,m>2090:A,rfg}
+qkq{x<1416:A,crn}
+qqz{s>2770:A,m<1801:hdj,R}
+rfg{s<537:R,x>2440:R,A}";
+        assert_eq!(format_workflows(&simplified), expected);
+        let recompiled = CompiledWorkflows::new(&simplified).unwrap();
+        assert_eq!(
+            recompiled.accepted_count(&all_parts()),
+            compiled.accepted_count(&all_parts())
+        );
+        assert_eq!(recompiled.unused, vec![]);
+    }
+
+    #[test]
+    fn unused_rules() {
+        let input =
+            parse("in{x>10:a,R}\na{x<5:R,m>0:A,x<3:R,A}\nb{A}\n\n{x=1,m=1,a=1,s=1}").unwrap();
+        let compiled = CompiledWorkflows::new(&input.workflows).unwrap();
+        let unused = [("a", 0), ("a", 2), ("a", 3), ("b", 0)];
+        assert_eq!(
+            compiled.unused,
+            unused.map(|(name, index)| (name.to_string(), index))
+        );
+        assert_eq!(
+            format_workflows(&compiled.simplify(&input.workflows)),
+            "in{x>10:A,R}"
+        );
+    }
+
+    #[test]
+    fn invalid_workflows() {
+        let input = parse("in{x>10:a,R}\n\n{x=1,m=1,a=1,s=1}").unwrap();
+        assert_eq!(part1(&input), Err(WorkflowError::Missing("a".to_string())));
+        let input = parse("px{A}\n\n{x=1,m=1,a=1,s=1}").unwrap();
+        assert_eq!(part2(&input), Err(WorkflowError::Missing("in".to_string())));
+        let input = parse("in{x>10:a,R}\na{m>5:A,b}\nb{in}\n\n{x=1,m=1,a=1,s=1}").unwrap();
+        let cycle = ["in", "a", "b", "in"].map(String::from).to_vec();
+        assert_eq!(part1(&input), Err(WorkflowError::Cycle(cycle)));
+    }
+
+    #[test]
+    fn unreachable_workflows() {
+        // Workflows that no part reaches may be broken
+        let input = parse("in{x>10:A,R}\na{b}\nb{m>5:a,c}\n\n{x=11,m=1,a=1,s=1}").unwrap();
+        assert_eq!(part1(&input), Ok(14));
+        assert_eq!(part2(&input), Ok(3990 * 4000 * 4000 * 4000));
     }
 }
@@ -2,6 +2,8 @@ use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{parse_lines, parse_number, split_once, strip_prefix, ParseError};
+
 #[derive(Debug, Clone)]
 struct Game {
     id: u32,
@@ -16,31 +18,31 @@ struct Set {
 }
 
 impl FromStr for Game {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (game, sets) = s.split_once(": ").unwrap();
-        let id = game.strip_prefix("Game ").unwrap().parse().unwrap();
-        let sets = sets.split("; ").map(|set| set.parse().unwrap()).collect();
+        let (game, sets) = split_once(s, ": ")?;
+        let id = parse_number(strip_prefix(game, "Game ")?)?;
+        let sets = sets.split("; ").map(str::parse).collect::<Result<_, _>>()?;
         Ok(Game { id, sets })
     }
 }
 
 impl FromStr for Set {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut red = 0;
         let mut green = 0;
         let mut blue = 0;
         for cubes in s.split(", ") {
-            let (count, color) = cubes.split_once(' ').unwrap();
-            let count = count.parse().unwrap();
+            let (count, color) = split_once(cubes, " ")?;
+            let count = parse_number(count)?;
             match color {
                 "red" => red = count,
                 "green" => green = count,
                 "blue" => blue = count,
-                _ => panic!("unknown color {color}"),
+                _ => return Err(ParseError::expected(color, "red, green or blue")),
             }
         }
         Ok(Set { red, green, blue })
@@ -48,8 +50,8 @@ impl FromStr for Set {
 }
 
 #[aoc_generator(day2)]
-fn parse(input: &str) -> Vec<Game> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Game>, ParseError> {
+    parse_lines(input).map_err(|err| err.locate(2, input))
 }
 
 impl Game {
@@ -106,11 +108,21 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 8);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 8);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 2286);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 2286);
+    }
+
+    #[test]
+    fn parse_error() {
+        let input = "Game 1: 3 blue, 4 red\nGame 2: 1 blue, 2 purple";
+        let error = parse(input).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "parse error in day 2 at line 2, column 19: expected red, green or blue"
+        );
     }
 }
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-#[derive(Debug, Clone)]
+use crate::util::{crt, find_cycle, split_once, Cycle, ParseError};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct Module {
     name: String,
     kind: ModuleKind,
@@ -10,20 +15,20 @@ struct Module {
     outputs: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum ModuleKind {
     FlipFlop(bool),
     Conjunction(Vec<bool>),
     Broadcast,
 }
 
-type ModuleGraph = HashMap<String, Module>;
+type ModuleGraph = BTreeMap<String, Module>;
 
 #[aoc_generator(day20)]
-fn parse(input: &str) -> ModuleGraph {
+fn parse(input: &str) -> Result<ModuleGraph, ParseError> {
     let mut graph = ModuleGraph::new();
     for line in input.lines() {
-        let (name, outputs) = line.split_once(" -> ").unwrap();
+        let (name, outputs) = split_once(line, " -> ").map_err(|err| err.locate(20, input))?;
         let (kind, name) = if name == "broadcaster" {
             (ModuleKind::Broadcast, name)
         } else if let Some(name) = name.strip_prefix('%') {
@@ -31,7 +36,7 @@ fn parse(input: &str) -> ModuleGraph {
         } else if let Some(name) = name.strip_prefix('&') {
             (ModuleKind::Conjunction(vec![]), name)
         } else {
-            panic!("invalid module: {name}")
+            return Err(ParseError::expected(name, "'%', '&' or broadcaster").locate(20, input));
         };
         let outputs = outputs.split(", ").map(|s| s.to_string()).collect();
         graph.insert(
@@ -58,7 +63,7 @@ fn parse(input: &str) -> ModuleGraph {
             }
         }
     }
-    graph
+    Ok(graph)
 }
 
 impl Module {
@@ -101,9 +106,39 @@ impl Module {
     fn is_conjunction(&self) -> bool {
         matches!(&self.kind, ModuleKind::Conjunction(_))
     }
+}
+
+/// When a pulse is handled during a button press.
+///
+/// Pulses are handled in the order they are sent, so all pulses sent at one time step
+/// are handled before the next, and those sent by the same pulse follow the order of
+/// its outputs. This orders them by their time, and then by the output taken at every
+/// step since the button was pressed.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct PulseOrder {
+    time: usize,
+    path: Vec<usize>,
+}
 
-    fn is_inverter(&self) -> bool {
-        matches!(&self.kind, ModuleKind::Conjunction(state) if state.len() == 1)
+impl PulseOrder {
+    /// Before any pulse of a press.
+    const START: PulseOrder = PulseOrder {
+        time: 0,
+        path: vec![],
+    };
+    /// After every pulse of a press.
+    const END: PulseOrder = PulseOrder {
+        time: usize::MAX,
+        path: vec![],
+    };
+
+    fn then(&self, output: usize) -> Self {
+        let mut path = self.path.clone();
+        path.push(output);
+        PulseOrder {
+            time: self.time + 1,
+            path,
+        }
     }
 }
 
@@ -112,7 +147,7 @@ struct Pulse {
     value: bool,
     sender: String,
     receiver: String,
-    time: usize,
+    order: PulseOrder,
 }
 
 fn push_button(graph: &mut ModuleGraph) -> Vec<Pulse> {
@@ -122,7 +157,7 @@ fn push_button(graph: &mut ModuleGraph) -> Vec<Pulse> {
         value: false,
         sender: "button".to_string(),
         receiver: "broadcaster".to_string(),
-        time: 0,
+        order: PulseOrder::START,
     });
     let mut index = 0;
     while index < pulses.len() {
@@ -132,11 +167,12 @@ fn push_button(graph: &mut ModuleGraph) -> Vec<Pulse> {
                 let new_pulses = receiver
                     .outputs
                     .iter()
-                    .map(|output| Pulse {
+                    .enumerate()
+                    .map(|(i, output)| Pulse {
                         value: new_pulse,
                         sender: receiver.name.clone(),
                         receiver: output.clone(),
-                        time: pulse.time + 1,
+                        order: pulse.order.then(i),
                     })
                     .collect::<Vec<_>>();
                 pulses.extend(new_pulses);
@@ -165,84 +201,310 @@ fn part1(graph: &ModuleGraph) -> u64 {
     low_pulses * high_pulses
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-struct State {
-    high_start: usize,
-    high_end: usize,
+/// The most button presses to simulate when the circuit can't be analyzed.
+const MAX_PRESSES: usize = 1_000_000;
+
+/// The most combinations of presses to consider when aligning the inputs of the final conjunction.
+const MAX_ALIGNMENTS: usize = 1_000_000;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CircuitError {
+    /// No module sends pulses to "rx".
+    NoRx,
+    /// The circuit could not be analyzed, and "rx" got no low pulse within the given presses.
+    NotFound { presses: usize },
+    /// "rx" will never receive a low pulse.
+    Never,
+    /// The inputs of the final conjunction are high together at too many different presses.
+    TooManyAlignments { limit: usize },
+}
+
+impl Display for CircuitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitError::NoRx => write!(f, "no module sends pulses to rx"),
+            CircuitError::NotFound { presses } => write!(
+                f,
+                "circuit does not decompose, and rx got no low pulse within {presses} presses"
+            ),
+            CircuitError::Never => write!(f, "rx never receives a low pulse"),
+            CircuitError::TooManyAlignments { limit } => write!(
+                f,
+                "inputs of the final conjunction can align in more than {limit} ways"
+            ),
+        }
+    }
+}
+
+impl Error for CircuitError {}
+
+/// Finds all modules that (directly or indirectly) send pulses to the given module,
+/// including the module itself but excluding the broadcaster.
+fn upstream_modules(graph: &ModuleGraph, name: &str) -> BTreeSet<String> {
+    let mut modules = BTreeSet::new();
+    let mut queue = vec![name.to_string()];
+    while let Some(name) = queue.pop() {
+        if name == "broadcaster" || modules.contains(&name) {
+            continue;
+        }
+        if let Some(module) = graph.get(&name) {
+            queue.extend(module.inputs.iter().cloned());
+        }
+        modules.insert(name);
+    }
+    modules
+}
+
+/// What one input of the final conjunction sends during a single button press.
+#[derive(Debug, Clone)]
+struct PressSignal {
+    /// The sorted, disjoint ranges of pulses after which the conjunction
+    /// remembers a high pulse from this input.
+    high: Vec<Range<PulseOrder>>,
+    /// The pulses that this input sends to the conjunction.
+    events: Vec<PulseOrder>,
+}
+
+impl PressSignal {
+    /// Combines the signals of two inputs: the conjunction remembers a high pulse for both
+    /// when it does for each of them, and it receives the pulses of both.
+    fn combine(&self, other: &PressSignal) -> PressSignal {
+        let mut high = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.high.len() && j < other.high.len() {
+            let (left, right) = (&self.high[i], &other.high[j]);
+            let start = left.start.clone().max(right.start.clone());
+            let end = left.end.clone().min(right.end.clone());
+            if start < end {
+                high.push(start..end);
+            }
+            if left.end < right.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        let events = self.events.iter().chain(&other.events).cloned().collect();
+        PressSignal { high, events }
+    }
+
+    /// Checks whether the conjunction sends a low pulse, which happens when it receives
+    /// a pulse after which it remembers a high pulse for all inputs.
+    fn sends_low(&self) -> bool {
+        self.events
+            .iter()
+            .any(|event| self.high.iter().any(|range| range.contains(event)))
+    }
 }
 
+/// The signals that one input of the final conjunction sends over all button presses.
 #[derive(Debug)]
-struct Cycle {
-    cycle_start: usize,
-    high_start: usize,
-    high_end: usize,
+struct InputTiming {
+    cycle: Cycle,
+    /// The signal during each press, up to the end of the first cycle.
+    presses: Vec<PressSignal>,
 }
 
-fn find_cycle(graph: &ModuleGraph, module_name: &String) -> Cycle {
-    let mut graph = graph.clone();
-    let mut seen_states = HashMap::<State, usize>::new();
-    let mut time = 0;
-    loop {
-        let pulses = push_button(&mut graph);
-        time += 1;
-        let high_pulse = pulses
+impl InputTiming {
+    /// Simulates the sub-circuit behind `input`, which sends its pulses to `target`.
+    ///
+    /// The sub-circuits only share the broadcaster, so their pulses keep the same order
+    /// as when the whole circuit is simulated.
+    fn analyze(graph: &ModuleGraph, input: &str, target: &str) -> Self {
+        let modules = upstream_modules(graph, input);
+        let subgraph = graph
             .iter()
-            .find(|pulse| &pulse.sender == module_name && pulse.value);
-        let high_pulse = if let Some(high_pulse) = high_pulse {
-            high_pulse
-        } else {
-            continue;
+            .filter(|(name, _)| *name == "broadcaster" || modules.contains(*name))
+            .map(|(name, module)| (name.clone(), module.clone()))
+            .collect::<ModuleGraph>();
+        // Track what the target remembers of the input between presses,
+        // since it doesn't belong to the sub-circuit.
+        let press = |(graph, remembered): &(ModuleGraph, bool)| {
+            let mut graph = graph.clone();
+            let pulses = push_button(&mut graph);
+            let mut remembered = *remembered;
+            let mut events = vec![];
+            let mut high = vec![];
+            let mut high_start = remembered.then_some(PulseOrder::START);
+            for pulse in pulses
+                .iter()
+                .filter(|pulse| pulse.sender == input && pulse.receiver == target)
+            {
+                events.push(pulse.order.clone());
+                match (pulse.value, high_start.take()) {
+                    (true, None) => high_start = Some(pulse.order.clone()),
+                    (false, Some(start)) => high.push(start..pulse.order.clone()),
+                    (_, start) => high_start = start,
+                }
+                remembered = pulse.value;
+            }
+            if let Some(start) = high_start {
+                high.push(start..PulseOrder::END);
+            }
+            let signal = PressSignal { high, events };
+            ((graph, remembered), signal)
         };
-        let low_pulse = pulses
+        let initial = (subgraph, false);
+        let cycle = find_cycle(initial.clone(), |state| press(state).0);
+        let mut presses = Vec::with_capacity(cycle.start + cycle.length);
+        let mut state = initial;
+        for _ in 0..cycle.start + cycle.length {
+            let (next_state, signal) = press(&state);
+            presses.push(signal);
+            state = next_state;
+        }
+        Self { cycle, presses }
+    }
+
+    fn signal_at(&self, press: usize) -> &PressSignal {
+        &self.presses[self.cycle.normalize(press)]
+    }
+}
+
+/// Presses at which some inputs of the final conjunction are high together.
+#[derive(Debug)]
+struct Alignment {
+    /// The presses `residue + modulus * k` for all `k`, counting within the cycles.
+    residue: i64,
+    modulus: i64,
+    /// The combined signal of the inputs at those presses.
+    signal: PressSignal,
+}
+
+/// Finds the first press at which all inputs of the final conjunction are high together.
+fn find_alignment(timings: &[InputTiming]) -> Result<usize, CircuitError> {
+    // Simulate until every input has gone through its cycle once.
+    let end = timings
+        .iter()
+        .map(|timing| timing.cycle.start + timing.cycle.length)
+        .max()
+        .unwrap_or(0);
+    for press in 0..end {
+        let signal = timings.iter().map(|timing| timing.signal_at(press)).fold(
+            None::<PressSignal>,
+            |combined, signal| {
+                Some(combined.map_or(signal.clone(), |combined| combined.combine(signal)))
+            },
+        );
+        if signal.is_some_and(|signal| signal.sends_low()) {
+            return Ok(press + 1);
+        }
+    }
+    // After that, all inputs are in their cycles. Add the inputs one by one,
+    // combining the presses at which they are high together using the CRT,
+    // and dropping presses at which they are never high at the same moment.
+    let mut alignments = vec![Alignment {
+        residue: 0,
+        modulus: 1,
+        signal: PressSignal {
+            high: vec![PulseOrder::START..PulseOrder::END],
+            events: vec![],
+        },
+    }];
+    for timing in timings {
+        let mut next_alignments = vec![];
+        for alignment in &alignments {
+            for press in (timing.cycle.start..timing.cycle.start + timing.cycle.length)
+                .filter(|&press| !timing.presses[press].high.is_empty())
+            {
+                let signal = alignment.signal.combine(&timing.presses[press]);
+                if signal.high.is_empty() {
+                    continue;
+                }
+                let congruences = [
+                    (alignment.residue, alignment.modulus),
+                    (press as i64, timing.cycle.length as i64),
+                ];
+                let Some((residue, modulus)) = crt(congruences) else {
+                    continue;
+                };
+                if next_alignments.len() == MAX_ALIGNMENTS {
+                    return Err(CircuitError::TooManyAlignments {
+                        limit: MAX_ALIGNMENTS,
+                    });
+                }
+                next_alignments.push(Alignment {
+                    residue,
+                    modulus,
+                    signal,
+                });
+            }
+        }
+        alignments = next_alignments;
+    }
+    alignments
+        .into_iter()
+        .filter(|alignment| alignment.signal.sends_low())
+        .map(
+            |Alignment {
+                 residue, modulus, ..
+             }| {
+                // Skip ahead to the first such press after all inputs have entered their cycles
+                let earliest = end as i64;
+                residue + (earliest - residue + modulus - 1).div_euclid(modulus) * modulus
+            },
+        )
+        .min()
+        .map(|press| press as usize + 1)
+        .ok_or(CircuitError::Never)
+}
+
+/// Tries to split the circuit into independent sub-circuits,
+/// each feeding into the single conjunction module that sends pulses to "rx".
+fn decompose(graph: &ModuleGraph, rx_senders: &[&Module]) -> Option<Vec<InputTiming>> {
+    let [conjunction] = rx_senders else {
+        return None;
+    };
+    if !conjunction.is_conjunction() {
+        return None;
+    }
+    let mut seen = BTreeSet::new();
+    for input in &conjunction.inputs {
+        let modules = upstream_modules(graph, input);
+        // The sub-circuits must not feed back into the conjunction, or into each other.
+        if modules.contains(&conjunction.name) || !modules.is_disjoint(&seen) {
+            return None;
+        }
+        seen.extend(modules);
+    }
+    let timings = conjunction
+        .inputs
+        .iter()
+        .map(|input| InputTiming::analyze(graph, input, &conjunction.name))
+        .collect();
+    Some(timings)
+}
+
+/// Presses the button until "rx" receives a low pulse.
+fn brute_force(graph: &ModuleGraph, max_presses: usize) -> Result<usize, CircuitError> {
+    let mut graph = graph.clone();
+    for press in 1..=max_presses {
+        let pulses = push_button(&mut graph);
+        if pulses
             .iter()
-            .find(|pulse| &pulse.sender == module_name && !pulse.value)
-            .expect("no low pulse found");
-        let state = State {
-            high_start: high_pulse.time,
-            high_end: low_pulse.time,
-        };
-        if let Some(&prev_time) = seen_states.get(&state) {
-            assert_eq!(prev_time, time - prev_time);
-            return Cycle {
-                cycle_start: prev_time,
-                high_start: state.high_start,
-                high_end: state.high_end,
-            };
-        } else {
-            seen_states.insert(state, time);
+            .any(|pulse| pulse.receiver == "rx" && !pulse.value)
+        {
+            return Ok(press);
         }
     }
+    Err(CircuitError::NotFound {
+        presses: max_presses,
+    })
 }
 
 #[aoc(day20, part2)]
-fn part2(graph: &ModuleGraph) -> usize {
-    // "rx" has a single input, "cl", which is a conjunction module.
-    let cl = graph
+fn part2(graph: &ModuleGraph) -> Result<usize, CircuitError> {
+    let rx_senders = graph
         .values()
-        .find(|module| module.outputs.contains(&("rx".to_string())))
-        .unwrap();
-    assert_eq!(cl.name, "cl");
-    assert!(cl.is_conjunction());
-    // "rx" will get a low pulse when "cl" has all high inputs.
-    // These inputs are all inverters. They send a high pulse when they receive a low pulse.
-    let cl_inputs = cl.inputs.clone();
-    let cl_input_modules = cl_inputs
-        .iter()
-        .map(|name| graph.get(name).unwrap())
+        .filter(|module| module.outputs.iter().any(|output| output == "rx"))
         .collect::<Vec<_>>();
-    assert!(cl_input_modules.iter().all(|input| input.is_inverter()));
-    // Figure out how often these inputs send a high pulse.
-    let cycles = cl_inputs
-        .iter()
-        .map(|cl_input| find_cycle(graph, cl_input))
-        .collect::<Vec<_>>();
-    // Assert: all inputs have matching time period for their high pulse
-    assert!(cycles
-        .iter()
-        .all(|cycle| cycle.high_start == cycles[0].high_start
-            && cycle.high_end == cycles[0].high_end));
-    // "rx" will get a low pulse when all these cycles align
-    cycles.iter().map(|cycle| cycle.cycle_start).product()
+    if rx_senders.is_empty() {
+        return Err(CircuitError::NoRx);
+    }
+    match decompose(graph, &rx_senders) {
+        Some(timings) => find_alignment(&timings),
+        None => brute_force(graph, MAX_PRESSES),
+    }
 }
 
 #[cfg(test)]
@@ -262,11 +524,73 @@ mod tests {
 
     #[test]
     fn part1_example1() {
-        assert_eq!(part1(&parse(EXAMPLE1)), 32_000_000);
+        assert_eq!(part1(&parse(EXAMPLE1).unwrap()), 32_000_000);
     }
 
     #[test]
     fn part1_example2() {
-        assert_eq!(part1(&parse(EXAMPLE2)), 11_687_500);
+        assert_eq!(part1(&parse(EXAMPLE2).unwrap()), 11_687_500);
+    }
+
+    #[test]
+    fn part2_counters() {
+        // "ia" goes high every 2 presses, "ib" every 4 presses
+        let input = "broadcaster -> fa, fb1
+%fa -> ia
+&ia -> f
+%fb1 -> fb2
+%fb2 -> ib
+&ib -> f
+&f -> rx";
+        let graph = parse(input).unwrap();
+        assert_eq!(part2(&graph), Ok(4));
+        assert_eq!(brute_force(&graph, 100), Ok(4));
+    }
+
+    #[test]
+    fn part2_offset_counters() {
+        // "ia" goes high on odd presses, "ib" every 4 presses
+        let input = "broadcaster -> fa, fb1
+%fa -> ia
+&ia -> ja
+&ja -> f
+%fb1 -> fb2
+%fb2 -> ib
+&ib -> f
+&f -> rx";
+        let graph = parse(input).unwrap();
+        assert_eq!(part2(&graph), Ok(5));
+        assert_eq!(brute_force(&graph, 100), Ok(5));
+    }
+
+    #[test]
+    fn part2_simultaneous_pulses() {
+        // At press 4, "ja" goes low and "ib" goes high at the same time step,
+        // so "f" sends a low pulse only if "ib" is handled first
+        let circuit = "%fa -> ia
+&ia -> ja
+&ja -> f
+%fb1 -> fb2
+%fb2 -> ib
+&ib -> f
+&f -> rx";
+        for (broadcaster, expected) in [("fa, fb1", 5), ("fb1, fa", 4)] {
+            let input = format!("broadcaster -> {broadcaster}\n{circuit}");
+            let graph = parse(&input).unwrap();
+            assert_eq!(part2(&graph), Ok(expected), "{broadcaster}");
+            assert_eq!(brute_force(&graph, 100), Ok(expected), "{broadcaster}");
+        }
+    }
+
+    #[test]
+    fn part2_fallback() {
+        // A flip-flop sends a low pulse every other press
+        let graph = parse("broadcaster -> a\n%a -> rx").unwrap();
+        assert_eq!(part2(&graph), Ok(2));
+    }
+
+    #[test]
+    fn part2_no_rx() {
+        assert_eq!(part2(&parse(EXAMPLE1).unwrap()), Err(CircuitError::NoRx));
     }
 }
@@ -1,45 +1,37 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use derivative::Derivative;
 use pathfinding::prelude::bfs_reach;
 
-use crate::util::Vector2D;
+use crate::util::{Grid, ParseError, Vector2D};
 
 struct Garden {
-    width: i32,
-    height: i32,
-    rocks: HashSet<Vector2D>,
+    rocks: Grid<bool>,
     start: Vector2D,
 }
 
 #[aoc_generator(day21)]
-fn parse(input: &str) -> Garden {
-    let height = input.lines().count() as i32;
-    let width = input.lines().next().unwrap().len() as i32;
-    let mut rocks = HashSet::new();
-    let mut start = None;
-    for (y, line) in input.lines().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let pos = Vector2D::new(x as i32, y as i32);
-            match c {
-                '.' => {}
-                '#' => {
-                    rocks.insert(pos);
-                }
-                'S' => {
-                    start = Some(pos);
-                }
-                _ => panic!("invalid tile: {c}"),
-            };
-        }
+fn parse(input: &str) -> Result<Garden, ParseError> {
+    let tiles = Grid::parse(input, "garden plot or rock", |c| match c {
+        '.' | '#' | 'S' => Some(c),
+        _ => None,
+    })
+    .map_err(|err| err.locate(21, input))?;
+    let start = tiles
+        .find(|&c| c == 'S')
+        .ok_or_else(|| ParseError::expected_after(input, "start position").locate(21, input))?;
+    let rocks = tiles.map(|&c| c == '#');
+    Ok(Garden { rocks, start })
+}
+
+impl Garden {
+    fn width(&self) -> i32 {
+        self.rocks.width() as i32
     }
-    let start = start.unwrap();
-    Garden {
-        width,
-        height,
-        rocks,
-        start,
+
+    fn height(&self) -> i32 {
+        self.rocks.height() as i32
     }
 }
 
@@ -60,10 +52,7 @@ fn find_reachable(garden: &Garden, start: Vector2D) -> impl Iterator<Item = Stat
         },
         move |&state| {
             state.pos.neighbours().filter_map(move |next_pos| {
-                if (0..garden.width).contains(&next_pos.x())
-                    && (0..garden.height).contains(&next_pos.y())
-                    && !garden.rocks.contains(&next_pos)
-                {
+                if garden.rocks.get(next_pos) == Some(&false) {
                     Some(State {
                         pos: next_pos,
                         steps: state.steps + 1,
@@ -91,50 +80,14 @@ fn part1(garden: &Garden) -> usize {
     count_reachable(garden, 64)
 }
 
-fn count_wrapping_reachable(garden: &Garden, steps: usize) -> usize {
-    let start = State {
-        pos: garden.start,
-        steps: 0,
-    };
-    let reachable = bfs_reach(start, |&state| {
-        state.pos.neighbours().filter_map(move |next_pos| {
-            let wrapped_pos = Vector2D::new(
-                next_pos.x().rem_euclid(garden.width),
-                next_pos.y().rem_euclid(garden.height),
-            );
-            if !garden.rocks.contains(&wrapped_pos) {
-                let next_state = State {
-                    pos: next_pos,
-                    steps: state.steps + 1,
-                };
-                Some(next_state)
-            } else {
-                None
-            }
-        })
-    });
-    let mut reachable_plots = HashSet::<Vector2D>::new();
-    let parity = steps % 2;
-    for state in reachable {
-        if state.steps > steps {
-            break;
-        }
-        // Must have same parity
-        if state.steps % 2 == parity {
-            reachable_plots.insert(state.pos);
-        }
-    }
-    reachable_plots.len()
-}
-
 fn count_wrapping_reachable_optimized(garden: &Garden, steps: usize) -> usize {
     // https://github.com/villuna/aoc23/wiki/A-Geometric-solution-to-advent-of-code-2023,-day-21
-    assert_eq!(garden.width, garden.height);
-    assert_eq!(garden.width % 2, 1);
+    assert_eq!(garden.width(), garden.height());
+    assert_eq!(garden.width() % 2, 1);
     // Distance to nearest edge is the same in all directions.
     // When we travel to the edge, the remaining number of steps is a multiple of the garden's size.
-    let distance_to_edge = steps % (garden.width as usize);
-    assert_eq!(distance_to_edge, (garden.width as usize) / 2);
+    let distance_to_edge = steps % (garden.width() as usize);
+    assert_eq!(distance_to_edge, (garden.width() as usize) / 2);
     let reachable = find_reachable(garden, garden.start)
         .map(|state| (state.pos, state.steps))
         .collect::<Vec<_>>();
@@ -163,7 +116,7 @@ fn count_wrapping_reachable_optimized(garden: &Garden, steps: usize) -> usize {
     assert_eq!(
         distance_to_edge,
         *distances
-            .get(&Vector2D::new(garden.width - 1, garden.start.y()))
+            .get(&Vector2D::new(garden.width() - 1, garden.start.y()))
             .unwrap()
     );
     assert_eq!(
@@ -173,14 +126,14 @@ fn count_wrapping_reachable_optimized(garden: &Garden, steps: usize) -> usize {
     assert_eq!(
         distance_to_edge,
         *distances
-            .get(&Vector2D::new(garden.start.x(), garden.height - 1))
+            .get(&Vector2D::new(garden.start.x(), garden.height() - 1))
             .unwrap()
     );
     // After traveling to the first square's edge, we'll travel an integer number of squares
     // in all directions. This forms a "diamond" pattern, where the last square in each direction
     // is only partially reachable.
-    assert_eq!((steps - distance_to_edge) % (garden.width as usize), 0);
-    let radius = (steps - distance_to_edge) / (garden.width as usize);
+    assert_eq!((steps - distance_to_edge) % (garden.width() as usize), 0);
+    let radius = (steps - distance_to_edge) / (garden.width() as usize);
     // We must perform an odd number of steps.
     // For the first square, we take the number of positions reachable in an odd number of steps.
     // Since the grid size is odd, the parity flips at every edge between two squares.
@@ -205,6 +158,8 @@ fn part2(garden: &Garden) -> usize {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use super::*;
 
     const EXAMPLE: &str = "...........
@@ -220,9 +175,46 @@ mod tests {
 ...........";
     const INPUT: &str = include_str!("../input/2023/day21.txt");
 
+    /// Counts the reachable plots by walking through the infinite garden.
+    fn count_wrapping_reachable(garden: &Garden, steps: usize) -> usize {
+        let start = State {
+            pos: garden.start,
+            steps: 0,
+        };
+        let reachable = bfs_reach(start, |&state| {
+            state.pos.neighbours().filter_map(move |next_pos| {
+                let wrapped_pos = Vector2D::new(
+                    next_pos.x().rem_euclid(garden.width()),
+                    next_pos.y().rem_euclid(garden.height()),
+                );
+                if !garden.rocks[wrapped_pos] {
+                    let next_state = State {
+                        pos: next_pos,
+                        steps: state.steps + 1,
+                    };
+                    Some(next_state)
+                } else {
+                    None
+                }
+            })
+        });
+        let mut reachable_plots = HashSet::<Vector2D>::new();
+        let parity = steps % 2;
+        for state in reachable {
+            if state.steps > steps {
+                break;
+            }
+            // Must have same parity
+            if state.steps % 2 == parity {
+                reachable_plots.insert(state.pos);
+            }
+        }
+        reachable_plots.len()
+    }
+
     #[test]
     fn part1_example() {
-        let garden = parse(EXAMPLE);
+        let garden = parse(EXAMPLE).unwrap();
         assert_eq!(count_reachable(&garden, 0), 1);
         assert_eq!(count_reachable(&garden, 1), 2);
         assert_eq!(count_reachable(&garden, 2), 4);
@@ -232,7 +224,7 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        let garden = parse(EXAMPLE);
+        let garden = parse(EXAMPLE).unwrap();
         assert_eq!(count_wrapping_reachable(&garden, 0), 1);
         assert_eq!(count_wrapping_reachable(&garden, 1), 2);
         assert_eq!(count_wrapping_reachable(&garden, 2), 4);
@@ -248,7 +240,7 @@ mod tests {
 
     #[test]
     fn part2_input() {
-        let garden = parse(INPUT);
+        let garden = parse(INPUT).unwrap();
         assert_eq!(count_wrapping_reachable(&garden, 131 + 65), 34234);
         assert_eq!(count_wrapping_reachable(&garden, 131 * 5 + 65), 459046);
         assert_eq!(count_wrapping_reachable(&garden, 131 * 10 + 65), 1672171);
@@ -256,7 +248,7 @@ mod tests {
 
     #[test]
     fn part2_input_optimized() {
-        let garden = parse(INPUT);
+        let garden = parse(INPUT).unwrap();
         assert_eq!(count_wrapping_reachable_optimized(&garden, 131 + 65), 34234);
         assert_eq!(
             count_wrapping_reachable_optimized(&garden, 131 * 5 + 65),
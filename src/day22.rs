@@ -1,153 +1,165 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::Vector3D;
+use crate::util::{parse_vector, split_once, Cuboid, ParseError, Vector3D};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-struct Brick {
-    start: Vector3D,
-    end: Vector3D,
-}
+type Brick = Cuboid<3>;
 
 #[aoc_generator(day22)]
-fn parse(input: &str) -> Vec<Brick> {
+fn parse(input: &str) -> Result<Vec<Brick>, ParseError> {
     input
         .lines()
         .map(|line| {
-            let (start, end) = line.split_once('~').unwrap();
-            let start = Vector3D::from_iter(start.split(',').map(|x| x.parse().unwrap()));
-            let end = Vector3D::from_iter(end.split(',').map(|x| x.parse().unwrap()));
-            assert!(start <= end);
-            Brick { start, end }
+            let (start, end) = split_once(line, "~")?;
+            let start_pos = parse_vector(start, ",")?;
+            let end_pos = parse_vector(end, ",")?;
+            if (0..3).any(|i| start_pos.coords[i] > end_pos.coords[i]) {
+                return Err(ParseError::expected(end, "end after start"));
+            }
+            Ok(Brick::from_inclusive(start_pos, end_pos))
         })
-        .collect()
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.locate(22, input))
 }
 
-impl Brick {
-    fn cubes(&self) -> Vec<Vector3D> {
-        let diff = self.end - self.start;
-        let length = diff.manhattan_distance() + 1;
-        let step = if diff.x() != 0 {
-            Vector3D::new(1, 0, 0)
-        } else if diff.y() != 0 {
-            Vector3D::new(0, 1, 0)
-        } else {
-            Vector3D::new(0, 0, 1)
-        };
-        let mut result = Vec::with_capacity(length as usize);
-        let mut pos = self.start;
-        while pos != self.end {
-            result.push(pos);
-            pos += step;
-        }
-        result.push(self.end);
-        result
-    }
-
-    fn can_drop(&self) -> bool {
-        self.start.z() > 1
-    }
-
-    fn drop(&self) -> Self {
-        debug_assert!(self.can_drop());
-        let mut result = *self;
-        *result.start.z_mut() -= 1;
-        *result.end.z_mut() -= 1;
-        result
-    }
+/// The bricks after they have settled, and which ones rest on which.
+///
+/// Bricks are sorted so that every brick comes after all bricks that support it.
+struct SupportGraph {
+    bricks: Vec<Brick>,
+    /// For each brick: the bricks resting directly on top of it.
+    supports: Vec<Vec<usize>>,
+    /// For each brick: the bricks directly below it, or nothing if it rests on the ground.
+    supported_by: Vec<Vec<usize>>,
 }
 
-fn try_drop_brick(brick: &Brick, dropped_cubes: &HashSet<Vector3D>) -> Option<Brick> {
-    if !brick.can_drop() {
-        return None;
+impl SupportGraph {
+    /// Drops all bricks as far down as they go, from lowest to highest.
+    fn settle(bricks: &[Brick]) -> Self {
+        let mut sorted_bricks = bricks.to_vec();
+        sorted_bricks.sort_by_key(|brick| brick.min.z());
+        // For each column: the top of the highest brick so far, and its index
+        let mut heights = HashMap::<(i32, i32), (i32, usize)>::new();
+        let mut settled = Vec::with_capacity(bricks.len());
+        let mut supports = vec![vec![]; bricks.len()];
+        let mut supported_by = vec![];
+        for (index, brick) in sorted_bricks.into_iter().enumerate() {
+            let columns = (brick.min.x()..brick.max.x())
+                .flat_map(|x| (brick.min.y()..brick.max.y()).map(move |y| (x, y)))
+                .collect::<Vec<_>>();
+            let floor = columns
+                .iter()
+                .filter_map(|column| heights.get(column))
+                .map(|&(top, _)| top)
+                .max()
+                .unwrap_or(1);
+            let mut below = columns
+                .iter()
+                .filter_map(|column| heights.get(column))
+                .filter(|&&(top, _)| top == floor)
+                .map(|&(_, other)| other)
+                .collect::<Vec<_>>();
+            below.sort();
+            below.dedup();
+            for &other in &below {
+                supports[other].push(index);
+            }
+            supported_by.push(below);
+            let brick = brick.translate(Vector3D::new(0, 0, floor - brick.min.z()));
+            for column in columns {
+                heights.insert(column, (brick.max.z(), index));
+            }
+            settled.push(brick);
+        }
+        Self {
+            bricks: settled,
+            supports,
+            supported_by,
+        }
     }
-    let next_brick = brick.drop();
-    if next_brick
-        .cubes()
-        .iter()
-        .any(|cube| dropped_cubes.contains(cube))
-    {
-        return None;
+
+    /// The bricks that can be disintegrated without any other brick falling.
+    fn removable(&self) -> Vec<usize> {
+        (0..self.bricks.len())
+            .filter(|&index| {
+                self.supports[index]
+                    .iter()
+                    .all(|&above| self.supported_by[above] != [index])
+            })
+            .collect()
     }
-    Some(next_brick)
-}
 
-fn drop_bricks(bricks: &[Brick]) -> (Vec<Brick>, HashSet<Vector3D>) {
-    // Drop bricks, from lowest to highest
-    let mut sorted_bricks = bricks.to_vec();
-    sorted_bricks.sort_by_key(|brick| brick.start.z());
-    let mut bricks = vec![];
-    let mut cubes = HashSet::<Vector3D>::new();
-    for mut brick in sorted_bricks {
-        while let Some(next_brick) = try_drop_brick(&brick, &cubes) {
-            brick = next_brick;
+    /// The bricks that fall when all of the `removed` bricks are disintegrated at once.
+    #[allow(dead_code)]
+    fn falling(&self, removed: &[usize]) -> Vec<usize> {
+        let mut fallen = vec![false; self.bricks.len()];
+        for &index in removed {
+            fallen[index] = true;
+        }
+        // All supporters come first, so they have already been handled
+        let mut falling = vec![];
+        for index in 0..self.bricks.len() {
+            let below = &self.supported_by[index];
+            if !fallen[index] && !below.is_empty() && below.iter().all(|&i| fallen[i]) {
+                fallen[index] = true;
+                falling.push(index);
+            }
         }
-        bricks.push(brick);
-        cubes.extend(brick.cubes());
+        falling
     }
-    bricks.sort_by_key(|brick| brick.start.z());
-    (bricks, cubes)
-}
 
-#[aoc(day22, part1)]
-fn part1(input: &[Brick]) -> usize {
-    let (bricks, cubes) = drop_bricks(input);
-    // Check which bricks can be disintegrated
-    let mut num_disintegrate = 0;
-    for brick_to_disintegrate in &bricks {
-        let mut can_disintegrate = true;
-        for other_brick in &bricks {
-            if other_brick == brick_to_disintegrate || !other_brick.can_drop() {
+    /// For each brick: how many other bricks fall when it is disintegrated.
+    ///
+    /// A brick falls exactly when one of its dominators is removed, where the dominators
+    /// are the bricks that every path of supports from the ground goes through.
+    /// https://en.wikipedia.org/wiki/Dominator_(graph_theory)
+    fn chain_reactions(&self) -> Vec<usize> {
+        let len = self.bricks.len();
+        // The immediate dominator of each brick, or `None` for the ground
+        let mut parent = vec![None::<usize>; len];
+        let mut depth = vec![0usize; len];
+        for index in 0..len {
+            // The immediate dominator is the lowest common ancestor of all supporters
+            let mut supporters = self.supported_by[index].iter().map(|&i| Some(i));
+            let Some(mut dominator) = supporters.next() else {
                 continue;
+            };
+            for mut other in supporters {
+                while dominator != other {
+                    let (Some(a), Some(b)) = (dominator, other) else {
+                        dominator = None;
+                        break;
+                    };
+                    if depth[a] >= depth[b] {
+                        dominator = parent[a];
+                    } else {
+                        other = parent[b];
+                    }
+                }
             }
-            // Disintegrate the brick
-            let mut new_cubes = cubes.clone();
-            for cube in brick_to_disintegrate.cubes() {
-                new_cubes.remove(&cube);
-            }
-            // Remove the other brick too, so it doesn't collide with itself
-            for cube in other_brick.cubes() {
-                new_cubes.remove(&cube);
-            }
-            // Check if the other brick would fall
-            if try_drop_brick(other_brick, &new_cubes).is_some() {
-                can_disintegrate = false;
-                break;
-            }
+            parent[index] = dominator;
+            depth[index] = dominator.map_or(1, |d| depth[d] + 1);
         }
-        if can_disintegrate {
-            num_disintegrate += 1;
+        // Everything in a brick's subtree falls with it
+        let mut subtree = vec![1usize; len];
+        for index in (0..len).rev() {
+            if let Some(dominator) = parent[index] {
+                subtree[dominator] += subtree[index];
+            }
         }
+        subtree.into_iter().map(|size| size - 1).collect()
     }
-    num_disintegrate
+}
+
+#[aoc(day22, part1)]
+fn part1(input: &[Brick]) -> usize {
+    SupportGraph::settle(input).removable().len()
 }
 
 #[aoc(day22, part2)]
 fn part2(input: &[Brick]) -> usize {
-    let (bricks, _) = drop_bricks(input);
-    // Check how many bricks would fall if we were to disintegrate every brick (separately)
-    let mut total_dropped = 0;
-    for &brick_to_disintegrate in &bricks {
-        // Let all other bricks drop again
-        let mut new_cubes = HashSet::new();
-        for &other_brick in &bricks {
-            if brick_to_disintegrate == other_brick {
-                continue;
-            }
-            let mut other_brick = other_brick;
-            let mut other_dropped = false;
-            while let Some(next_brick) = try_drop_brick(&other_brick, &new_cubes) {
-                other_brick = next_brick;
-                other_dropped = true;
-            }
-            new_cubes.extend(other_brick.cubes());
-            if other_dropped {
-                total_dropped += 1;
-            }
-        }
-    }
-    total_dropped
+    SupportGraph::settle(input).chain_reactions().iter().sum()
 }
 
 #[cfg(test)]
@@ -164,11 +176,36 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 5);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 5);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 7);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 7);
+    }
+
+    #[test]
+    fn parse_reversed_brick() {
+        let input = "0,2,1~1,0,1";
+        let error = parse(input).unwrap_err();
+        assert_eq!((error.line, error.column), (Some(1), Some(7)));
+    }
+
+    #[test]
+    fn support_graph() {
+        let graph = SupportGraph::settle(&parse(INPUT).unwrap());
+        assert_eq!(graph.supports[0], vec![1, 2]);
+        assert_eq!(graph.supported_by[5], vec![3, 4]);
+        assert_eq!(graph.bricks[6].min.z(), 5);
+        assert_eq!(graph.removable(), vec![1, 2, 3, 4, 6]);
+        assert_eq!(graph.chain_reactions(), vec![6, 0, 0, 0, 0, 1, 0]);
+        for index in 0..graph.bricks.len() {
+            assert_eq!(
+                graph.falling(&[index]).len(),
+                graph.chain_reactions()[index]
+            );
+        }
+        assert_eq!(graph.falling(&[1, 2]), vec![3, 4, 5, 6]);
+        assert_eq!(graph.falling(&[3]), vec![]);
     }
 }
@@ -1,8 +1,10 @@
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::{Direction, Vector2D};
+use crate::util::{Direction, Grid, ParseError, Vector2D};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Tile {
@@ -11,29 +13,18 @@ enum Tile {
     Slope(Direction),
 }
 
-type Map = HashMap<Vector2D, Tile>;
+type Map = Grid<Tile>;
 
 #[aoc_generator(day23)]
-fn parse(input: &str) -> Map {
-    input
-        .lines()
-        .enumerate()
-        .flat_map(move |(y, line)| {
-            line.chars().enumerate().map(move |(x, c)| {
-                let pos = Vector2D::new(x as i32, y as i32);
-                let tile = match c {
-                    '.' => Tile::Path,
-                    '#' => Tile::Forest,
-                    '^' => Tile::Slope(Direction::N),
-                    '>' => Tile::Slope(Direction::E),
-                    'v' => Tile::Slope(Direction::S),
-                    '<' => Tile::Slope(Direction::W),
-                    _ => panic!("invalid tile {c}"),
-                };
-                (pos, tile)
-            })
+fn parse(input: &str) -> Result<Map, ParseError> {
+    Grid::parse(input, "path, forest or slope", |c| {
+        Some(match c {
+            '.' => Tile::Path,
+            '#' => Tile::Forest,
+            _ => Tile::Slope(Direction::from_arrow(c)?),
         })
-        .collect()
+    })
+    .map_err(|err| err.locate(23, input))
 }
 
 struct Crossing {
@@ -44,7 +35,7 @@ type CrossingMap = HashMap<Vector2D, Crossing>;
 
 fn get_neighbour(pos: Vector2D, dir: Direction, map: &Map, part2: bool) -> Option<(Vector2D, u64)> {
     let next_pos = pos + dir.step();
-    let next_tile = map.get(&next_pos)?;
+    let next_tile = map.get(next_pos)?;
     Some(match next_tile {
         Tile::Path => (next_pos, 1),
         Tile::Slope(_) if part2 => (next_pos, 1),
@@ -112,6 +103,15 @@ fn reduce_map(map: &Map, start: Vector2D, goal: Vector2D, part2: bool) -> Crossi
 }
 
 impl Crossing {
+    /// Checks whether moving in `dir` would go back along the outer edge of the crossing grid.
+    ///
+    /// Once a path reaches a crossing on the edge, going back towards the start along that
+    /// edge can never reach the goal without getting stuck, so those moves are never taken.
+    fn is_backtracking(&self, dir: Direction) -> bool {
+        (self.is_vertical_edge() && dir == Direction::N)
+            || (self.is_horizontal_edge() && dir == Direction::W)
+    }
+
     fn is_vertical_edge(&self) -> bool {
         !self.neighbours.contains_key(&Direction::W) || !self.neighbours.contains_key(&Direction::E)
     }
@@ -121,60 +121,233 @@ impl Crossing {
     }
 }
 
-fn dfs_longest(
-    start: Vector2D,
-    goal: Vector2D,
-    seen: Vec<Vector2D>,
-    map: &CrossingMap,
-) -> Option<u64> {
-    let crossing = map.get(&start).unwrap();
-    crossing
-        .neighbours
-        .iter()
-        .filter_map(|(&next_dir, &(next_pos, next_cost))| {
-            if next_pos == goal {
-                return Some(next_cost);
+/// The crossing map, with crossings numbered from zero so visited sets fit in a bitset.
+struct CrossingGraph {
+    positions: Vec<Vector2D>,
+    /// For each crossing: the crossings it leads to, and the number of steps to get there.
+    neighbours: Vec<Vec<(usize, u64)>>,
+    /// For each crossing: the most steps it takes to get there from any other crossing.
+    max_incoming: Vec<u64>,
+    start: usize,
+    goal: usize,
+}
+
+/// A longest path, as the crossings it visits in order.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct LongestPath {
+    length: u64,
+    crossings: Vec<Vector2D>,
+}
+
+/// A partially explored path.
+#[derive(Debug, Clone)]
+struct SearchState {
+    node: usize,
+    /// One bit per crossing, in words of 64 bits.
+    visited: Vec<u64>,
+    length: u64,
+    /// Upper bound on the number of steps left, from all crossings not yet visited.
+    remaining: u64,
+    path: Vec<usize>,
+}
+
+impl SearchState {
+    fn is_visited(&self, node: usize) -> bool {
+        self.visited[node / 64] & (1 << (node % 64)) != 0
+    }
+}
+
+impl CrossingGraph {
+    fn new(map: &CrossingMap, start: Vector2D, goal: Vector2D) -> Self {
+        let mut positions = map.keys().copied().collect::<Vec<_>>();
+        positions.sort_by_key(|pos| (pos.y(), pos.x()));
+        let index_of = |pos: &Vector2D| positions.iter().position(|p| p == pos).unwrap();
+        let neighbours = positions
+            .iter()
+            .map(|pos| {
+                let crossing = &map[pos];
+                let mut neighbours = crossing
+                    .neighbours
+                    .iter()
+                    .filter(|&(&next_dir, &(next_pos, _))| {
+                        next_pos == goal || !crossing.is_backtracking(next_dir)
+                    })
+                    .map(|(_, &(next_pos, cost))| (index_of(&next_pos), cost))
+                    .collect::<Vec<_>>();
+                neighbours.sort();
+                neighbours
+            })
+            .collect::<Vec<_>>();
+        let mut max_incoming = vec![0; positions.len()];
+        for &(next, cost) in neighbours.iter().flatten() {
+            max_incoming[next] = max_incoming[next].max(cost);
+        }
+        Self {
+            start: index_of(&start),
+            goal: index_of(&goal),
+            positions,
+            neighbours,
+            max_incoming,
+        }
+    }
+
+    fn initial_state(&self) -> SearchState {
+        SearchState {
+            node: self.start,
+            visited: {
+                let mut visited = vec![0; self.positions.len().div_ceil(64)];
+                visited[self.start / 64] |= 1 << (self.start % 64);
+                visited
+            },
+            length: 0,
+            remaining: self.max_incoming.iter().sum::<u64>() - self.max_incoming[self.start],
+            path: vec![self.start],
+        }
+    }
+
+    fn next_states<'a>(&'a self, state: &'a SearchState) -> impl Iterator<Item = SearchState> + 'a {
+        self.neighbours[state.node]
+            .iter()
+            .filter(|&&(next, _)| !state.is_visited(next))
+            .map(|&(next, cost)| {
+                let mut path = state.path.clone();
+                path.push(next);
+                let mut visited = state.visited.clone();
+                visited[next / 64] |= 1 << (next % 64);
+                SearchState {
+                    node: next,
+                    visited,
+                    length: state.length + cost,
+                    remaining: state.remaining - self.max_incoming[next],
+                    path,
+                }
+            })
+    }
+
+    /// Finds the longest path to the goal that starts from `state`, but only if it is longer
+    /// than the best path found so far (by any thread).
+    fn dfs_longest(&self, state: &mut SearchState, best: &AtomicU64) -> Option<LongestPath> {
+        if state.node == self.goal {
+            best.fetch_max(state.length, Ordering::Relaxed);
+            return Some(LongestPath {
+                length: state.length,
+                crossings: state
+                    .path
+                    .iter()
+                    .map(|&node| self.positions[node])
+                    .collect(),
+            });
+        }
+        if state.length + state.remaining <= best.load(Ordering::Relaxed) {
+            // Can't beat the best path anymore
+            return None;
+        }
+        let mut longest = None::<LongestPath>;
+        for &(next, cost) in &self.neighbours[state.node] {
+            if state.is_visited(next) {
+                continue;
             }
-            if seen.contains(&next_pos) {
-                return None;
+            let (node, remaining) = (state.node, state.remaining);
+            state.node = next;
+            state.visited[next / 64] ^= 1 << (next % 64);
+            state.length += cost;
+            state.remaining -= self.max_incoming[next];
+            state.path.push(next);
+            if let Some(path) = self.dfs_longest(state, best) {
+                if longest.as_ref().map_or(true, |l| path.length > l.length) {
+                    longest = Some(path);
+                }
             }
-            if crossing.is_vertical_edge() && next_dir == Direction::N {
-                return None;
+            state.path.pop();
+            state.remaining = remaining;
+            state.length -= cost;
+            state.visited[next / 64] ^= 1 << (next % 64);
+            state.node = node;
+        }
+        longest
+    }
+
+    /// Finds the longest path from start to goal that visits every crossing at most once.
+    ///
+    /// With more than one thread, the search first expands the paths near the start,
+    /// and then lets the threads explore the branches from there.
+    fn longest_path(&self, threads: usize) -> Option<LongestPath> {
+        let best = AtomicU64::new(0);
+        let mut branches = vec![self.initial_state()];
+        if threads > 1 {
+            while branches.len() < threads * 8 {
+                let next_branches = branches
+                    .iter()
+                    .flat_map(|state| {
+                        if state.node == self.goal {
+                            vec![state.clone()]
+                        } else {
+                            self.next_states(state).collect()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if next_branches.len() <= branches.len() {
+                    break;
+                }
+                branches = next_branches;
             }
-            if crossing.is_horizontal_edge() && next_dir == Direction::W {
-                return None;
+        }
+        let next_branch = AtomicUsize::new(0);
+        let search = || {
+            let mut longest = None::<LongestPath>;
+            while let Some(state) = branches.get(next_branch.fetch_add(1, Ordering::Relaxed)) {
+                if let Some(path) = self.dfs_longest(&mut state.clone(), &best) {
+                    if longest.as_ref().map_or(true, |l| path.length > l.length) {
+                        longest = Some(path);
+                    }
+                }
             }
-            let mut next_seen = seen.clone();
-            next_seen.push(next_pos);
-            let next_longest = next_cost + dfs_longest(next_pos, goal, next_seen, map)?;
-            Some(next_longest)
+            longest
+        };
+        if threads <= 1 {
+            return search();
+        }
+        thread::scope(|scope| {
+            let handles = (0..threads)
+                .map(|_| scope.spawn(search))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().unwrap())
+                .max_by_key(|path| path.length)
         })
-        .max()
+    }
 }
 
-fn solve(map: &Map, part2: bool) -> u64 {
-    let max_y = map.keys().map(|pos| pos.y()).max().unwrap();
-    let (&start, _) = map
-        .iter()
-        .find(|(pos, &tile)| pos.y() == 0 && tile == Tile::Path)
-        .unwrap();
-    let (&goal, _) = map
-        .iter()
-        .find(|(pos, &tile)| pos.y() == max_y && tile == Tile::Path)
-        .unwrap();
+fn solve(map: &Map, part2: bool, threads: usize) -> LongestPath {
+    let find_path = |y: usize| {
+        let x = map
+            .row(y)
+            .iter()
+            .position(|&tile| tile == Tile::Path)
+            .unwrap();
+        Vector2D::new(x as i32, y as i32)
+    };
+    let start = find_path(0);
+    let goal = find_path(map.height() - 1);
     let map = reduce_map(map, start, goal, part2);
+    let graph = CrossingGraph::new(&map, start, goal);
 
-    dfs_longest(start, goal, vec![start], &map).unwrap()
+    graph.longest_path(threads).expect("no path to the goal")
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
 }
 
 #[aoc(day23, part1)]
 fn part1(map: &Map) -> u64 {
-    solve(map, false)
+    solve(map, false, 1).length
 }
 
 #[aoc(day23, part2)]
 fn part2(map: &Map) -> u64 {
-    solve(map, true)
+    solve(map, true, available_threads()).length
 }
 
 #[cfg(test)]
@@ -207,11 +380,40 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 94);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 94);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 154);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 154);
+    }
+
+    #[test]
+    fn longest_path_threads() {
+        let map = parse(INPUT).unwrap();
+        for part2 in [false, true] {
+            let sequential = solve(&map, part2, 1);
+            let parallel = solve(&map, part2, 4);
+            assert_eq!(sequential.length, parallel.length);
+            assert_eq!(sequential.crossings.first(), Some(&Vector2D::new(1, 0)));
+            assert_eq!(sequential.crossings.last(), Some(&Vector2D::new(21, 22)));
+        }
+    }
+
+    #[test]
+    fn many_crossings() {
+        // Two corridors joined by many one-way rungs, with a crossing at the top of each rung
+        let rungs = 70;
+        let width = 2 * rungs + 3;
+        let input = [
+            format!("#.{}", "#".repeat(width - 2)),
+            format!("#.{}#", ">.".repeat(rungs)),
+            format!("##{}#", "#v".repeat(rungs)),
+            format!("###.{}#", ">.".repeat(rungs - 1)),
+            format!("{}.#", "#".repeat(width - 2)),
+        ]
+        .join("\n");
+        let map = parse(&input).unwrap();
+        assert_eq!(part1(&map), width as u64 + 1);
     }
 }
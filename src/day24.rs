@@ -1,10 +1,12 @@
-use std::io::{Read, Write};
-use std::process::{Command, Stdio};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{gcd, parse_vector, split_once, Num, ParseError, Ratio};
+
 type Vector3D = crate::util::Vector3D<i64>;
-type FloatVector2D = crate::util::Vector2D<f64>;
+type WideVector3D = crate::util::Vector3D<i128>;
 type FloatVector3D = crate::util::Vector3D<f64>;
 
 #[derive(Debug, Copy, Clone)]
@@ -14,38 +16,35 @@ struct Hailstone {
 }
 
 #[aoc_generator(day24)]
-fn parse(input: &str) -> Vec<Hailstone> {
+fn parse(input: &str) -> Result<Vec<Hailstone>, ParseError> {
     input
         .lines()
         .map(|s| {
-            let (pos, vel) = s.split_once(" @ ").unwrap();
-            let pos = Vector3D::from_iter(pos.split(", ").map(|x| x.trim().parse().unwrap()));
-            let vel = Vector3D::from_iter(vel.split(", ").map(|x| x.trim().parse().unwrap()));
-            Hailstone { pos, vel }
+            let (pos, vel) = split_once(s, " @ ")?;
+            let pos = parse_vector(pos, ", ")?;
+            let vel = parse_vector(vel, ", ")?;
+            Ok(Hailstone { pos, vel })
         })
-        .collect()
+        .collect::<Result<_, _>>()
+        .map_err(|err: ParseError| err.locate(24, input))
 }
 
 impl Hailstone {
-    fn intersect_2d(&self, other: &Self) -> Option<FloatVector2D> {
-        self.intersect(other, true).map(|pos| pos.into_2d())
-    }
-
-    #[allow(unused)]
-    fn intersect_3d(&self, other: &Self) -> Option<FloatVector3D> {
-        self.intersect(other, false)
-    }
-
-    fn intersect(&self, other: &Self, skip_z: bool) -> Option<FloatVector3D> {
+    /// Finds the times at which both hailstones pass through the same point in the XY plane,
+    /// using `convert` to compute with numbers of type `T`.
+    fn crossing_times_2d<T: Num>(
+        &self,
+        other: &Self,
+        convert: impl Fn(i64) -> T,
+    ) -> Option<(T, T)> {
         // Parametric equations for X coordinates of line 1 and 2:
         // x1 = pos1.x + vel1.x * t1
         // x2 = pos2.x + vel2.x * t2
         // X coordinates must be equal for some values of t1 and t2:
         // pos1.x + vel1.x * t1 = pos2.x + vel2.x * t2
         // t1 = ((pos2.x - pos1.x) + (vel2.x * t2)) / vel1.x  (1)
-        // Same for Y and Z:
+        // Same for Y:
         // t1 = ((pos2.y - pos1.y) + (vel2.y * t2)) / vel1.y  (2)
-        // t1 = ((pos2.z - pos1.z) + (vel2.z * t2)) / vel1.z  (3)
         // Setting (1) and (2) equal:
         // t2 = ((vel1.x * (pos1.y - pos2.y) - vel1.y * (pos1.x - pos2.x)) / (vel1.x * vel2.y - vel2.x * vel1.y)
         let determinant_vel = self.vel.x() * other.vel.y() - other.vel.x() * self.vel.y();
@@ -53,36 +52,98 @@ impl Hailstone {
             // Parallel
             return None;
         }
-        let pos1 = self.pos.to_f64();
-        let pos2 = other.pos.to_f64();
-        let vel1 = self.vel.to_f64();
-        let vel2 = other.vel.to_f64();
+        let pos1 = self.pos.map(&convert);
+        let pos2 = other.pos.map(&convert);
+        let vel1 = self.vel.map(&convert);
+        let vel2 = other.vel.map(&convert);
         let t2 = (vel1.x() * (pos1.y() - pos2.y()) - vel1.y() * (pos1.x() - pos2.x()))
-            / (determinant_vel as f64);
-        // Substitute in (1) to find t1:
-        let t1 = ((pos2.x() - pos1.x()) + (vel2.x() * t2)) / vel1.x();
-        let mut intersect1 = pos1 + vel1 * t1;
-        let mut intersect2 = pos2 + vel2 * t2;
-        // Ignore Z for part 1
-        if skip_z {
-            *intersect1.z_mut() = 0.0;
-            *intersect2.z_mut() = 0.0;
+            / convert(determinant_vel);
+        // Substitute in (1) or (2) to find t1, whichever doesn't divide by zero
+        let t1 = if self.vel.x() != 0 {
+            ((pos2.x() - pos1.x()) + (vel2.x() * t2)) / vel1.x()
+        } else {
+            ((pos2.y() - pos1.y()) + (vel2.y() * t2)) / vel1.y()
+        };
+        Some((t1, t2))
+    }
+
+    /// Checks whether both hailstones' paths cross in the future, within the given test area.
+    fn crosses_within<T: Num>(
+        &self,
+        other: &Self,
+        min: T,
+        max: T,
+        convert: impl Fn(i64) -> T,
+    ) -> bool {
+        let Some((t1, t2)) = self.crossing_times_2d(other, &convert) else {
+            return false;
+        };
+        // Must not be in the past
+        if t1 <= T::zero() || t2 <= T::zero() {
+            return false;
+        }
+        // Must be within target area
+        let intersection = self.pos.map(&convert) + self.vel.map(&convert) * t1;
+        (min..=max).contains(&intersection.x()) && (min..=max).contains(&intersection.y())
+    }
+
+    /// Checks whether both hailstones' paths cross in the future, within the given test area.
+    ///
+    /// Floating point numbers are fast, but may round the wrong way when the crossing
+    /// is close to the edge of the test area or to the start, so then this uses exact
+    /// rational numbers instead.
+    fn crosses_within_area(&self, other: &Self, min: i64, max: i64) -> bool {
+        let Some((t1, t2)) = self.crossing_times_2d(other, |x| x as f64) else {
+            return false;
+        };
+        let intersection = self.pos.to_f64() + self.vel.to_f64() * t1;
+        // The rounding errors grow with the size of the positions and velocities
+        let scale = [
+            min,
+            max,
+            self.pos.x(),
+            self.pos.y(),
+            other.pos.x(),
+            other.pos.y(),
+        ]
+        .into_iter()
+        .map(|x| (x as f64).abs())
+        .fold(1.0, f64::max);
+        let speed = [self.vel.x(), self.vel.y(), other.vel.x(), other.vel.y()]
+            .into_iter()
+            .map(|x| (x as f64).abs())
+            .fold(1.0, f64::max);
+        let time_tolerance = 1e-12 * scale * speed;
+        let pos_tolerance = time_tolerance * speed;
+        let is_close = |coord: f64| {
+            (coord - min as f64).abs() <= pos_tolerance
+                || (coord - max as f64).abs() <= pos_tolerance
+        };
+        if t1.abs() <= time_tolerance
+            || t2.abs() <= time_tolerance
+            || is_close(intersection.x())
+            || is_close(intersection.y())
+        {
+            return self.crosses_within(other, Ratio::from(min), Ratio::from(max), Ratio::from);
         }
+        let area = min as f64..=max as f64;
+        t1 > 0.0 && t2 > 0.0 && area.contains(&intersection.x()) && area.contains(&intersection.y())
+    }
+
+    #[allow(unused)]
+    fn intersect_3d(&self, other: &Self) -> Option<FloatVector3D> {
+        let (t1, t2) = self.crossing_times_2d(other, |x| x as f64)?;
+        let intersect1 = self.pos.to_f64() + self.vel.to_f64() * t1;
+        let intersect2 = other.pos.to_f64() + other.vel.to_f64() * t2;
         // Check if it also works for Z
         if !intersect1.relative_eq(&intersect2, 1e-12) {
             return None;
         }
         Some(intersect1)
     }
-
-    fn time_for(&self, pos: FloatVector2D) -> f64 {
-        // x = start_pos.x + vel.x * t
-        // t = (x - start_pos.x) / vel.x
-        (pos.x() - (self.pos.x()) as f64) / (self.vel.x() as f64)
-    }
 }
 
-fn count_intersections(hailstones: &[Hailstone], min: f64, max: f64) -> usize {
+fn count_intersections(hailstones: &[Hailstone], min: i64, max: i64) -> usize {
     hailstones
         .iter()
         .enumerate()
@@ -92,106 +153,211 @@ fn count_intersections(hailstones: &[Hailstone], min: f64, max: f64) -> usize {
                 .skip(i + 1)
                 .map(move |right| (left, right))
         })
-        .filter_map(|(left, right)| {
-            let intersection = left.intersect_2d(right)?;
-            // Must be within target area
-            if (min..=max).contains(&intersection.x()) && (min..=max).contains(&intersection.y()) {
-                // Must not be in the past
-                let left_time = left.time_for(intersection);
-                let right_time = right.time_for(intersection);
-                if left_time > 0.0 && right_time > 0.0 {
-                    return Some(intersection);
-                }
-            }
-            None
-        })
+        .filter(|(left, right)| left.crosses_within_area(right, min, max))
         .count()
 }
 
 #[aoc(day24, part1)]
 fn part1(hailstones: &[Hailstone]) -> usize {
-    count_intersections(hailstones, 200_000_000_000_000.0, 400_000_000_000_000.0)
-}
-
-fn make_z3_equation(
-    coord: char,
-    hailstone_pos: i64,
-    hailstone_vel: i64,
-    time_index: usize,
-) -> String {
-    format!("(assert (= (+ (* time_{time_index} vel_{coord}) pos_{coord}) (+ (* time_{time_index} {hailstone_vel}) {hailstone_pos})))\n")
-}
-
-fn make_z3_script(hailstones: &[Hailstone]) -> String {
-    let mut script = String::new();
-    // The rock's position
-    script.push_str("(declare-const pos_x Int)\n");
-    script.push_str("(declare-const pos_y Int)\n");
-    script.push_str("(declare-const pos_z Int)\n");
-    // The rock's velocity
-    script.push_str("(declare-const vel_x Int)\n");
-    script.push_str("(declare-const vel_y Int)\n");
-    script.push_str("(declare-const vel_z Int)\n");
-    // The collision time with each hailstone
-    for (i, _hailstone) in hailstones.iter().enumerate() {
-        script.push_str(&format!("(declare-const time_{i} Int)\n"));
-    }
-    // Set up equations
-    for (i, hailstone) in hailstones.iter().enumerate() {
-        // Collide in all three coordinates
-        script.push_str(&make_z3_equation(
-            'x',
-            hailstone.pos.x(),
-            hailstone.vel.x(),
-            i,
-        ));
-        script.push_str(&make_z3_equation(
-            'y',
-            hailstone.pos.y(),
-            hailstone.vel.y(),
-            i,
-        ));
-        script.push_str(&make_z3_equation(
-            'z',
-            hailstone.pos.z(),
-            hailstone.vel.z(),
-            i,
-        ));
+    count_intersections(hailstones, 200_000_000_000_000, 400_000_000_000_000)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Rock {
+    pos: Vector3D,
+    vel: Vector3D,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ThrowError {
+    /// The hailstones don't pin down a single trajectory for the rock.
+    Underdetermined,
+    /// There is no integer trajectory that hits every hailstone.
+    NoSolution,
+}
+
+impl Display for ThrowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrowError::Underdetermined => write!(f, "rock trajectory is underdetermined"),
+            ThrowError::NoSolution => write!(f, "no rock trajectory hits every hailstone"),
+        }
+    }
+}
+
+impl Error for ThrowError {}
+
+/// Divides a vector by the GCD of its coordinates.
+fn reduce(v: WideVector3D) -> WideVector3D {
+    let divisor = gcd(gcd(v.x(), v.y()), v.z());
+    if divisor == 0 {
+        v
+    } else {
+        v / divisor
     }
-    script.push_str("(check-sat)\n");
-    script.push_str("(get-model)\n");
-    script
 }
 
-fn read_z3_constant(output: &str, name: &str) -> i64 {
-    let mut lines = output.lines();
-    let pattern = format!("(define-fun {name} () Int");
-    while let Some(line) = lines.next() {
-        if line.contains(&pattern) {
-            let next_line = lines.next().unwrap();
-            let value = next_line.trim().strip_suffix(')').unwrap();
-            return value.parse().unwrap();
+/// Divides two vectors that are known to be parallel, i.e. finds `t` such that `a = b * t`.
+fn divide_parallel(a: WideVector3D, b: WideVector3D) -> Result<i128, ThrowError> {
+    let i = (0..3).find(|&i| b.coords[i] != 0).unwrap();
+    let t = a.coords[i]
+        .checked_div(b.coords[i])
+        .ok_or(ThrowError::NoSolution)?;
+    if checked_mul(b, t)? == a {
+        Ok(t)
+    } else {
+        Err(ThrowError::NoSolution)
+    }
+}
+
+// The coordinates of the input fit in an i64, but their products can still overflow an i128.
+// A trajectory that needs such large numbers can't fit in an i64 either, so overflow means
+// there is no solution.
+
+fn checked_add(a: WideVector3D, b: WideVector3D) -> Result<WideVector3D, ThrowError> {
+    let mut result = WideVector3D::zero();
+    for i in 0..3 {
+        result.coords[i] = a.coords[i]
+            .checked_add(b.coords[i])
+            .ok_or(ThrowError::NoSolution)?;
+    }
+    Ok(result)
+}
+
+fn checked_mul(v: WideVector3D, t: i128) -> Result<WideVector3D, ThrowError> {
+    let mut result = WideVector3D::zero();
+    for i in 0..3 {
+        result.coords[i] = v.coords[i].checked_mul(t).ok_or(ThrowError::NoSolution)?;
+    }
+    Ok(result)
+}
+
+fn checked_dot(a: WideVector3D, b: WideVector3D) -> Result<i128, ThrowError> {
+    (0..3).try_fold(0i128, |sum, i| {
+        a.coords[i]
+            .checked_mul(b.coords[i])
+            .and_then(|product| sum.checked_add(product))
+            .ok_or(ThrowError::NoSolution)
+    })
+}
+
+fn checked_cross(a: WideVector3D, b: WideVector3D) -> Result<WideVector3D, ThrowError> {
+    let coord = |i: usize, j: usize| {
+        a.coords[i]
+            .checked_mul(b.coords[j])?
+            .checked_sub(a.coords[j].checked_mul(b.coords[i])?)
+    };
+    let mut result = WideVector3D::zero();
+    for (k, (i, j)) in [(1, 2), (2, 0), (0, 1)].into_iter().enumerate() {
+        result.coords[k] = coord(i, j).ok_or(ThrowError::NoSolution)?;
+    }
+    Ok(result)
+}
+
+fn find_rock(hailstones: &[Hailstone]) -> Result<Rock, ThrowError> {
+    let [first, others @ ..] = hailstones else {
+        return Err(ThrowError::Underdetermined);
+    };
+    // Work in the reference frame of the first hailstone, where it sits still at the origin.
+    // The rock must pass through the origin, so its line lies in the plane through the origin
+    // that contains another hailstone's line. Two such planes intersect in the rock's line.
+    let first_pos = first.pos.map(i128::from);
+    let first_vel = first.vel.map(i128::from);
+    let relative = others
+        .iter()
+        .map(|hailstone| {
+            (
+                hailstone,
+                hailstone.pos.map(i128::from) - first_pos,
+                hailstone.vel.map(i128::from) - first_vel,
+            )
+        })
+        .collect::<Vec<_>>();
+    let normals = relative
+        .iter()
+        .map(|&(_, pos, vel)| checked_cross(pos, vel).map(reduce))
+        .filter(|normal| *normal != Ok(WideVector3D::zero()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dir = normals
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &left)| normals[i + 1..].iter().map(move |&right| (left, right)))
+        .map(|(left, right)| checked_cross(left, right).map(reduce))
+        .find(|dir| *dir != Ok(WideVector3D::zero()))
+        .ok_or(ThrowError::Underdetermined)??;
+    // Find the time at which each hailstone crosses the rock's line, i.e. the time t
+    // for which (pos + vel * t) is parallel to dir.
+    let mut collisions = Vec::with_capacity(relative.len());
+    for &(hailstone, pos, vel) in &relative {
+        let pos_cross = checked_cross(pos, dir)?;
+        let vel_cross = checked_cross(vel, dir)?;
+        if vel_cross == WideVector3D::zero() {
+            if pos_cross == WideVector3D::zero() {
+                // Always on the rock's line, so any time would do
+                continue;
+            } else {
+                // Never on the rock's line
+                return Err(ThrowError::NoSolution);
+            }
         }
+        let time = divide_parallel(checked_mul(pos_cross, -1)?, vel_cross)?;
+        collisions.push((
+            time,
+            checked_add(
+                hailstone.pos.map(i128::from),
+                checked_mul(hailstone.vel.map(i128::from), time)?,
+            )?,
+        ));
     }
-    panic!("no variable named {name} in z3 output")
+    // Two collisions at different times determine the rock's trajectory.
+    let (&(left_time, left_pos), &(right_time, right_pos)) = collisions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, left)| collisions[i + 1..].iter().map(move |right| (left, right)))
+        .find(|(left, right)| left.0 != right.0)
+        .ok_or(ThrowError::Underdetermined)?;
+    let vel = checked_add(right_pos, checked_mul(left_pos, -1)?)?;
+    let time = right_time
+        .checked_sub(left_time)
+        .ok_or(ThrowError::NoSolution)?;
+    if vel.map(|x| x % time) != WideVector3D::zero() {
+        return Err(ThrowError::NoSolution);
+    }
+    let vel = vel / time;
+    let pos = checked_add(left_pos, checked_mul(vel, -left_time)?)?;
+    // Check that the rock actually hits every hailstone in the future.
+    for hailstone in hailstones {
+        let pos_diff = checked_add(hailstone.pos.map(i128::from), checked_mul(pos, -1)?)?;
+        let vel_diff = checked_add(vel, checked_mul(hailstone.vel.map(i128::from), -1)?)?;
+        // The hailstone must be ahead of the rock, and they must close in on each other,
+        // unless they start at the same position
+        let never_meets = if vel_diff == WideVector3D::zero() {
+            pos_diff != WideVector3D::zero()
+        } else {
+            checked_cross(pos_diff, vel_diff)? != WideVector3D::zero()
+                || checked_dot(pos_diff, vel_diff)? < 0
+        };
+        if never_meets {
+            return Err(ThrowError::NoSolution);
+        }
+    }
+    let narrow = |v: WideVector3D| -> Result<Vector3D, ThrowError> {
+        let mut result = Vector3D::zero();
+        for i in 0..3 {
+            result.coords[i] = v.coords[i].try_into().map_err(|_| ThrowError::NoSolution)?;
+        }
+        Ok(result)
+    };
+    Ok(Rock {
+        pos: narrow(pos)?,
+        vel: narrow(vel)?,
+    })
 }
 
 #[aoc(day24, part2)]
-fn part2(hailstones: &[Hailstone]) -> i64 {
-    let script = make_z3_script(hailstones);
-    let process = Command::new("z3")
-        .arg("-in") // read from standard input
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("failed to spawn z3");
-    process.stdin.unwrap().write_all(script.as_bytes()).unwrap();
-    let mut output = String::new();
-    process.stdout.unwrap().read_to_string(&mut output).unwrap();
-    let pos_x = read_z3_constant(&output, "pos_x");
-    let pos_y = read_z3_constant(&output, "pos_y");
-    let pos_z = read_z3_constant(&output, "pos_z");
-    pos_x + pos_y + pos_z
+fn part2(hailstones: &[Hailstone]) -> Result<i64, ThrowError> {
+    let rock = find_rock(hailstones)?;
+    Ok(rock.pos.x() + rock.pos.y() + rock.pos.z())
 }
 
 #[cfg(test)]
@@ -206,11 +372,92 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(count_intersections(&parse(INPUT), 7.0, 27.0), 2);
+        let hailstones = parse(INPUT).unwrap();
+        assert_eq!(count_intersections(&hailstones, 7, 27), 2);
+    }
+
+    #[test]
+    fn part1_exact_boundary() {
+        // These paths cross just outside the test area, at x = 4e14 + 0.0138...,
+        // but floating point numbers round the crossing onto the edge
+        let hailstones = [
+            Hailstone {
+                pos: Vector3D::new(708_272_451_384_213, -28_446_161_574_636, 0),
+                vel: Vector3D::new(-428, 379, 0),
+            },
+            Hailstone {
+                pos: Vector3D::new(502_469_107_626_446, 288_969_071_379_377, 0),
+                vel: Vector3D::new(-422, -183, 0),
+            },
+        ];
+        let min = 200_000_000_000_000;
+        let max = 400_000_000_000_000;
+        let [left, right] = &hailstones;
+        assert!(left.crosses_within(right, min as f64, max as f64, |x| x as f64));
+        assert!(!left.crosses_within(right, Ratio::from(min), Ratio::from(max), Ratio::from));
+        assert_eq!(count_intersections(&hailstones, min, max), 0);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 47);
+        assert_eq!(
+            find_rock(&parse(INPUT).unwrap()),
+            Ok(Rock {
+                pos: Vector3D::new(24, 13, 10),
+                vel: Vector3D::new(-3, 1, 2),
+            })
+        );
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(47));
+    }
+
+    #[test]
+    fn part2_underdetermined() {
+        let hailstones = parse(INPUT).unwrap();
+        assert_eq!(
+            find_rock(&hailstones[..2]),
+            Err(ThrowError::Underdetermined)
+        );
+    }
+
+    #[test]
+    fn part2_no_solution() {
+        let mut hailstones = parse(INPUT).unwrap();
+        *hailstones[4].pos.x_mut() += 1;
+        assert_eq!(find_rock(&hailstones), Err(ThrowError::NoSolution));
+    }
+
+    #[test]
+    fn part2_same_velocity() {
+        // A hailstone moving alongside the rock, on the same line, never hits it
+        let mut hailstones = parse(INPUT).unwrap();
+        hailstones.push(Hailstone {
+            pos: Vector3D::new(18, 13, 34),
+            vel: Vector3D::new(-3, 1, 2),
+        });
+        assert_eq!(find_rock(&hailstones), Err(ThrowError::NoSolution));
+    }
+
+    #[test]
+    fn part2_overflow() {
+        let big = i64::MAX;
+        let hailstones = [
+            Hailstone {
+                pos: Vector3D::new(0, 0, 0),
+                vel: Vector3D::new(0, 0, 0),
+            },
+            Hailstone {
+                pos: Vector3D::new(big, 0, 1),
+                vel: Vector3D::new(0, big, -big),
+            },
+            Hailstone {
+                pos: Vector3D::new(-big, big, 0),
+                vel: Vector3D::new(big, 1, big),
+            },
+            Hailstone {
+                pos: Vector3D::new(1, -big, big),
+                vel: Vector3D::new(-big, big, 2),
+            },
+        ];
+        assert_eq!(find_rock(&hailstones), Err(ThrowError::NoSolution));
     }
 }
@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use nohash_hasher::IntMap;
-use rand::seq::IteratorRandom;
-use rand::SeedableRng;
+
+use crate::util::{split_once, ParseError};
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 struct NodeId(u64);
@@ -13,47 +15,68 @@ impl nohash_hasher::IsEnabled for NodeId {}
 #[derive(Debug, Default, Clone)]
 struct Graph {
     connections: IntMap<NodeId, Vec<NodeId>>,
-    weights: IntMap<NodeId, usize>,
-}
-
-impl Graph {
-    fn add(&mut self, from: NodeId, to: NodeId) {
-        self.connections.entry(from).or_default().push(to);
-        self.connections.entry(to).or_default().push(from);
-        self.weights.entry(from).or_insert(1);
-        self.weights.entry(to).or_insert(1);
-    }
+    /// The name of each component, indexed by its id.
+    names: Vec<String>,
 }
 
 #[aoc_generator(day25)]
-fn parse(input: &str) -> Graph {
-    let mut graph = Graph::default();
-    let mut get_id = {
-        let mut ids = HashMap::<String, NodeId>::new();
-        let mut next_id = 0u64;
-        move |name: &str| -> NodeId {
-            *ids.entry(name.to_string()).or_insert_with(|| {
-                let id = next_id;
-                next_id += 1;
-                NodeId(id)
-            })
-        }
+fn parse(input: &str) -> Result<Graph, ParseError> {
+    let mut connections = IntMap::<NodeId, Vec<NodeId>>::default();
+    let mut names = vec![];
+    let mut ids = HashMap::<&str, NodeId>::new();
+    let mut get_id = |name| {
+        *ids.entry(name).or_insert_with(|| {
+            names.push(name.to_string());
+            NodeId(names.len() as u64 - 1)
+        })
     };
 
     for line in input.lines() {
-        let (from, connections) = line.split_once(": ").unwrap();
+        let (from, to_names) = split_once(line, ": ").map_err(|err| err.locate(25, input))?;
         let from_id = get_id(from);
-        for to in connections.split(' ') {
-            graph.add(from_id, get_id(to));
+        for to in to_names.split(' ') {
+            let to_id = get_id(to);
+            connections.entry(from_id).or_default().push(to_id);
+            connections.entry(to_id).or_default().push(from_id);
         }
     }
-    graph
+    Ok(Graph { connections, names })
+}
+
+/// There is no cut with the requested number of edges.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct NoCut {
+    size: usize,
+    /// The cut with the fewest edges instead, if the graph has at least two nodes.
+    min_cut: Option<Cut>,
 }
 
+impl Display for NoCut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no cut with {} edges", self.size)?;
+        match &self.min_cut {
+            Some(cut) if cut.edges.is_empty() => write!(f, ", the graph is already split"),
+            Some(cut) => {
+                write!(f, ", the smallest cut is")?;
+                for (from, to) in &cut.edges {
+                    write!(f, " {from}/{to}")?;
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for NoCut {}
+
 #[aoc(day25, part1)]
-fn part1(graph: &Graph) -> usize {
-    let (left, right) = split_graph(graph);
-    left * right
+fn part1(graph: &Graph) -> Result<usize, NoCut> {
+    let cut = graph.cut_of_size(3).ok_or_else(|| NoCut {
+        size: 3,
+        min_cut: graph.min_cut(),
+    })?;
+    Ok(cut.sizes.0 * cut.sizes.1)
 }
 
 impl Graph {
@@ -71,55 +94,130 @@ impl Graph {
         })
     }
 
-    /// Remove the edge `(from, to)` from the graph, and replace it with a new node
-    /// that is connected with all nodes connected to `from` or `to` in the original graph.
-    /// https://en.wikipedia.org/wiki/Karger%27s_algorithm#Contraction_algorithm
-    fn contract(&mut self, from: NodeId, to: NodeId) {
-        // Merge `from` connections with `to` connections.
-        let to_connections = self.connections.remove(&to).unwrap();
-        let from_connections = self.connections.get_mut(&from).unwrap();
-        from_connections.extend(to_connections);
-        from_connections.retain(|x| x != &from && x != &to); // no self-loops
-
-        // Merge weights.
-        *self.weights.get_mut(&from).unwrap() += self.weights.remove(&to).unwrap();
-
-        // Replace `to` with `from` in the rest of the graph.
-        for (_, connections) in self.connections.iter_mut() {
-            for other in connections.iter_mut() {
-                if *other == to {
-                    *other = from;
+    /// Finds a cut with exactly `size` edges, if one exists that is also
+    /// a minimum cut between some pair of nodes.
+    fn cut_of_size(&self, size: usize) -> Option<Cut> {
+        let network = FlowNetwork::new(self);
+        // Any cut separates the first node from some other node
+        (1..network.nodes.len()).find_map(|sink| {
+            network
+                .min_cut_between(0, sink, size)
+                .filter(|cut| cut.edges.len() == size)
+        })
+    }
+
+    /// Finds a cut with the fewest edges, if the graph has at least two nodes.
+    fn min_cut(&self) -> Option<Cut> {
+        let network = FlowNetwork::new(self);
+        let mut best = None::<Cut>;
+        for sink in 1..network.nodes.len() {
+            let limit = best.as_ref().map_or(usize::MAX, |cut| cut.edges.len() - 1);
+            if let Some(cut) = network.min_cut_between(0, sink, limit) {
+                let is_empty = cut.edges.is_empty();
+                best = Some(cut);
+                if is_empty {
+                    break;
                 }
             }
         }
+        best
     }
 }
 
-const SEED: u64 = 1;
-
-/// https://en.wikipedia.org/wiki/Karger's_algorithm
-fn split_graph(input: &Graph) -> (usize, usize) {
-    let mut rng = rand::rngs::StdRng::seed_from_u64(SEED);
-    let mut attempts = 0;
-    loop {
-        let mut graph = input.clone();
-        while graph.connections.len() > 2 {
-            let (from, to) = graph.edges().choose(&mut rng).unwrap();
-            graph.contract(from, to);
+/// A set of edges that splits the graph in two, with the number of nodes on each side.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Cut {
+    /// The names of the components at both ends of each edge.
+    edges: Vec<(String, String)>,
+    sizes: (usize, usize),
+}
+
+/// The graph with nodes numbered from zero, where every edge has capacity one.
+struct FlowNetwork<'a> {
+    /// The name of each node.
+    nodes: Vec<&'a str>,
+    /// For each node: its neighbours, with the index of the connecting edge.
+    adjacency: Vec<Vec<(usize, usize)>>,
+    /// For each edge: its lowest and highest node.
+    edges: Vec<(usize, usize)>,
+}
+
+impl<'a> FlowNetwork<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let mut nodes = graph.connections.keys().copied().collect::<Vec<_>>();
+        nodes.sort();
+        let index_of = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, &node)| (node, index))
+            .collect::<IntMap<_, _>>();
+        let mut adjacency = vec![vec![]; nodes.len()];
+        let mut edges = graph
+            .edges()
+            .map(|(from, to)| (index_of[&from], index_of[&to]))
+            .collect::<Vec<_>>();
+        edges.sort();
+        for (index, &(from, to)) in edges.iter().enumerate() {
+            adjacency[from].push((to, index));
+            adjacency[to].push((from, index));
         }
-        // Only two nodes left in the contracted graph
-        // Check if they're separated by exactly 3 edges
-        assert_eq!(graph.connections.len(), 2);
-        let connections = graph.connections.values().next().unwrap();
-        if connections.len() == 3 {
-            // Found it!
-            let weights = graph.weights.values().cloned().collect::<Vec<_>>();
-            assert_eq!(weights.len(), 2);
-            println!("Found after {attempts} attempts with seed {SEED}");
-            return (weights[0], weights[1]);
-        } else {
-            // Accidentally merged the wrong two nodes. Try again.
-            attempts += 1;
+        Self {
+            nodes: nodes
+                .iter()
+                .map(|node| graph.names[node.0 as usize].as_str())
+                .collect(),
+            adjacency,
+            edges,
+        }
+    }
+
+    /// Finds a minimum cut between `source` and `sink`, unless it has more than `limit` edges.
+    ///
+    /// https://en.wikipedia.org/wiki/Edmonds%E2%80%93Karp_algorithm
+    fn min_cut_between(&self, source: usize, sink: usize, limit: usize) -> Option<Cut> {
+        // Flow through each edge, from its lowest to its highest node
+        let mut flow = vec![0i8; self.edges.len()];
+        let direction = |from: usize, edge: usize| if self.edges[edge].0 == from { 1 } else { -1 };
+        let mut flow_size = 0;
+        loop {
+            // Find a path with capacity left, using breadth-first search
+            let mut parents = vec![None; self.nodes.len()];
+            let mut reached = vec![false; self.nodes.len()];
+            let mut queue = VecDeque::from([source]);
+            reached[source] = true;
+            while let Some(node) = queue.pop_front() {
+                for &(next, edge) in &self.adjacency[node] {
+                    if !reached[next] && direction(node, edge) * flow[edge] < 1 {
+                        reached[next] = true;
+                        parents[next] = Some((node, edge));
+                        queue.push_back(next);
+                    }
+                }
+            }
+            if !reached[sink] {
+                // No more paths: the nodes still reachable from the source form one side
+                let edges = self
+                    .edges
+                    .iter()
+                    .filter(|&&(from, to)| reached[from] != reached[to])
+                    .map(|&(from, to)| (self.nodes[from].to_string(), self.nodes[to].to_string()))
+                    .collect();
+                let left = reached.iter().filter(|&&reached| reached).count();
+                return Some(Cut {
+                    edges,
+                    sizes: (left, self.nodes.len() - left),
+                });
+            }
+            if flow_size == limit {
+                return None;
+            }
+            // Push one unit of flow along the path
+            let mut node = sink;
+            while let Some((parent, edge)) = parents[node] {
+                flow[edge] += direction(parent, edge);
+                node = parent;
+            }
+            flow_size += 1;
         }
     }
 }
@@ -144,8 +242,49 @@ frs: qnr lhk lsr";
 
     #[test]
     fn part1_example() {
-        let graph = parse(INPUT);
-        let (left, right) = split_graph(&graph);
-        assert_eq!(left * right, 54);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(54));
+    }
+
+    #[test]
+    fn min_cut_example() {
+        let graph = parse(INPUT).unwrap();
+        let cut = graph.min_cut().unwrap();
+        let mut edges = cut
+            .edges
+            .iter()
+            .map(|(from, to)| (from.min(to).clone(), from.max(to).clone()))
+            .collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(
+            edges,
+            [("bvb", "cmg"), ("hfx", "pzl"), ("jqt", "nvd")]
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+        );
+        assert_eq!(cut.sizes.0 * cut.sizes.1, 54);
+        assert_eq!(graph.cut_of_size(3), Some(cut));
+        assert_eq!(graph.cut_of_size(2), None);
+    }
+
+    #[test]
+    fn min_cut_disconnected() {
+        let graph = parse("a: b\nc: d e").unwrap();
+        let cut = graph.min_cut().unwrap();
+        assert_eq!(cut.edges, vec![]);
+        assert_eq!(cut.sizes, (2, 3));
+        let err = part1(&graph).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no cut with 3 edges, the graph is already split"
+        );
+    }
+
+    #[test]
+    fn no_cut_of_size() {
+        let graph = parse("a: b c\nb: c\nc: d").unwrap();
+        let err = part1(&graph).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no cut with 3 edges, the smallest cut is c/d"
+        );
     }
 }
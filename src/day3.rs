@@ -3,7 +3,7 @@ use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::Vector2D;
+use crate::util::{Grid, ParseError, Vector2D};
 
 #[derive(Debug)]
 struct Schematic {
@@ -17,14 +17,15 @@ struct EngineThing {
 }
 
 impl FromStr for Schematic {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut numbers = Vec::new();
         let mut things = HashMap::new();
-        for (y, line) in s.lines().enumerate() {
+        let grid = Grid::parse(s, "digit, symbol or '.'", Some)?;
+        for (y, row) in grid.rows().enumerate() {
             let mut current_number: Option<(Vector2D, String)> = None;
-            for (x, c) in line.chars().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
                 let pos = Vector2D::new(x as i32, y as i32);
                 if c.is_ascii_digit() {
                     let (_, number) = current_number.get_or_insert((pos, String::new()));
@@ -81,8 +82,10 @@ impl Schematic {
 }
 
 #[aoc_generator(day3)]
-fn parse(input: &str) -> Schematic {
-    input.parse().unwrap()
+fn parse(input: &str) -> Result<Schematic, ParseError> {
+    input
+        .parse()
+        .map_err(|err: ParseError| err.locate(3, input))
 }
 
 #[aoc(day3, part1)]
@@ -121,11 +124,11 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 4361);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 4361);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 467835);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 467835);
     }
 }
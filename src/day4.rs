@@ -2,6 +2,8 @@ use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{parse_lines, parse_number, split_once, ParseError};
+
 #[derive(Debug)]
 struct Scratchcard {
     winning: Vec<u32>,
@@ -9,20 +11,27 @@ struct Scratchcard {
 }
 
 impl FromStr for Scratchcard {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (_id, numbers) = s.split_once(": ").unwrap();
-        let (winning, have) = numbers.split_once(" | ").unwrap();
-        let winning = winning.split(' ').filter_map(|x| x.parse().ok()).collect();
-        let have = have.split(' ').filter_map(|x| x.parse().ok()).collect();
+        let (_id, numbers) = split_once(s, ": ")?;
+        let (winning, have) = split_once(numbers, " | ")?;
+        let winning = parse_numbers(winning)?;
+        let have = parse_numbers(have)?;
         Ok(Self { winning, have })
     }
 }
 
+fn parse_numbers(s: &str) -> Result<Vec<u32>, ParseError> {
+    s.split(' ')
+        .filter(|x| !x.is_empty())
+        .map(parse_number)
+        .collect()
+}
+
 #[aoc_generator(day4)]
-fn parse(input: &str) -> Vec<Scratchcard> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Scratchcard>, ParseError> {
+    parse_lines(input).map_err(|err| err.locate(4, input))
 }
 
 impl Scratchcard {
@@ -73,11 +82,11 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 13);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 13);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 30);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 30);
     }
 }
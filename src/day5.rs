@@ -1,9 +1,14 @@
-use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
 use std::ops::Range;
 use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{
+    array_windows, next_item, parse_number, split_once, strip_prefix, strip_suffix, IntervalSet,
+    ParseError,
+};
+
 #[derive(Debug, Clone)]
 struct Almanac {
     seeds: Vec<i64>,
@@ -16,12 +21,14 @@ struct Almanac {
     humid_to_loc: Mapping,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 struct Mapping {
+    source: String,
+    dest: String,
     entries: Vec<MappingEntry>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 struct MappingEntry {
     dest_start: i64,
     source_start: i64,
@@ -29,19 +36,24 @@ struct MappingEntry {
 }
 
 impl FromStr for Almanac {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut sections = s.split("\n\n");
-        let seeds = sections.next().unwrap().strip_prefix("seeds: ").unwrap();
-        let seeds = seeds.split(' ').map(|x| x.parse().unwrap()).collect();
-        let seed_to_soil = sections.next().unwrap().parse().unwrap();
-        let soil_to_fert = sections.next().unwrap().parse().unwrap();
-        let fert_to_water = sections.next().unwrap().parse().unwrap();
-        let water_to_light = sections.next().unwrap().parse().unwrap();
-        let light_to_temp = sections.next().unwrap().parse().unwrap();
-        let temp_to_humid = sections.next().unwrap().parse().unwrap();
-        let humid_to_loc = sections.next().unwrap().parse().unwrap();
+        let seeds = next_item(&mut sections, s, "seeds")?;
+        let seeds = strip_prefix(seeds, "seeds: ")?;
+        let seeds = seeds
+            .split(' ')
+            .map(parse_number)
+            .collect::<Result<_, _>>()?;
+        let mut next_mapping = || next_item(&mut sections, s, "mapping")?.parse();
+        let seed_to_soil = next_mapping()?;
+        let soil_to_fert = next_mapping()?;
+        let fert_to_water = next_mapping()?;
+        let water_to_light = next_mapping()?;
+        let light_to_temp = next_mapping()?;
+        let temp_to_humid = next_mapping()?;
+        let humid_to_loc = next_mapping()?;
         Ok(Self {
             seeds,
             seed_to_soil,
@@ -56,23 +68,30 @@ impl FromStr for Almanac {
 }
 
 impl FromStr for Mapping {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines = s.lines().skip(1);
-        let entries = lines.map(|line| line.parse().unwrap()).collect();
-        Ok(Self { entries })
+        let mut lines = s.lines();
+        let header = strip_suffix(next_item(&mut lines, s, "mapping header")?, " map:")?;
+        let (source, dest) = split_once(header, "-to-")?;
+        let entries = lines.map(str::parse).collect::<Result<_, _>>()?;
+        Ok(Self {
+            source: source.to_string(),
+            dest: dest.to_string(),
+            entries,
+        })
     }
 }
 
 impl FromStr for MappingEntry {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut numbers = s.split(' ').map(|x| x.parse().unwrap());
-        let dest_start = numbers.next().unwrap();
-        let source_start = numbers.next().unwrap();
-        let range_length = numbers.next().unwrap();
+        let mut numbers = s.split(' ');
+        let mut next_number = || parse_number(next_item(&mut numbers, s, "number")?);
+        let dest_start = next_number()?;
+        let source_start = next_number()?;
+        let range_length = next_number()?;
         Ok(Self {
             dest_start,
             source_start,
@@ -82,153 +101,225 @@ impl FromStr for MappingEntry {
 }
 
 #[aoc_generator(day5)]
-fn parse(input: &str) -> Almanac {
-    input.parse().unwrap()
+fn parse(input: &str) -> Result<Almanac, ParseError> {
+    input
+        .parse()
+        .map_err(|err: ParseError| err.locate(5, input))
 }
 
-impl Mapping {
-    fn map(&self, value: i64) -> i64 {
+impl Display for Mapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-to-{} map:", self.source, self.dest)?;
         for entry in &self.entries {
-            if (entry.source_start..entry.source_start + entry.range_length).contains(&value) {
-                return value - entry.source_start + entry.dest_start;
-            }
+            write!(
+                f,
+                "\n{} {} {}",
+                entry.dest_start, entry.source_start, entry.range_length
+            )?;
         }
-        value
+        Ok(())
     }
 }
 
-impl Almanac {
-    fn seed_to_loc(&self, seed: i64) -> i64 {
-        let soil = self.seed_to_soil.map(seed);
-        let fert = self.soil_to_fert.map(soil);
-        let water = self.fert_to_water.map(fert);
-        let light = self.water_to_light.map(water);
-        let temp = self.light_to_temp.map(light);
-        let humid = self.temp_to_humid.map(temp);
-
-        self.humid_to_loc.map(humid)
+impl MappingEntry {
+    fn source_range(&self) -> Range<i64> {
+        self.source_start..self.source_start + self.range_length
     }
-}
 
-#[aoc(day5, part1)]
-fn part1(input: &Almanac) -> i64 {
-    input
-        .seeds
-        .iter()
-        .map(|seed| input.seed_to_loc(*seed))
-        .min()
-        .unwrap()
+    fn offset(&self) -> i64 {
+        self.dest_start - self.source_start
+    }
 }
 
-type Interval = Range<i64>;
+/// A range of values that all move by the same offset.
+type Piece = (Range<i64>, i64);
 
-fn compare_interval(a: &Interval, b: &Interval) -> Ordering {
-    a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
-}
-
-#[derive(Debug, Clone)]
-struct IntervalSet {
-    intervals: Vec<Interval>,
-}
+impl Mapping {
+    fn map(&self, value: i64) -> i64 {
+        for entry in &self.entries {
+            if entry.source_range().contains(&value) {
+                return value + entry.offset();
+            }
+        }
+        value
+    }
 
-impl IntervalSet {
-    fn from(intervals: Vec<Interval>) -> Self {
-        let mut set = Self { intervals };
-        set.merge_overlapping();
-        set
+    fn map_ranges(&self, ranges: IntervalSet<i64>) -> IntervalSet<i64> {
+        let mut remainder = ranges;
+        let mut shifted = IntervalSet::new();
+        for entry in &self.entries {
+            let source = IntervalSet::from(entry.source_range());
+            shifted = shifted.union(&remainder.intersection(&source).shift(entry.offset()));
+            remainder = remainder.difference(&source);
+        }
+        remainder.union(&shifted)
     }
 
-    fn add_all(&mut self, new_ranges: &[Interval]) {
-        self.intervals.extend_from_slice(new_ranges);
-        self.merge_overlapping();
+    /// All values that are moved by this mapping, as disjoint pieces sorted by their source.
+    /// When entries overlap, the first one wins.
+    fn pieces(&self) -> Vec<Piece> {
+        let mut pieces = self
+            .entries
+            .iter()
+            .map(|entry| (entry.source_range(), entry.offset()))
+            .filter(|(range, _)| !range.is_empty())
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|(range, _)| range.start);
+        // Usually no entries overlap, so there is nothing to resolve
+        if array_windows(&pieces).all(|[(left, _), (right, _)]| left.end <= right.start) {
+            return pieces;
+        }
+        let mut covered = IntervalSet::new();
+        pieces.clear();
+        for entry in &self.entries {
+            let source = IntervalSet::from(entry.source_range()).difference(&covered);
+            pieces.extend(
+                source
+                    .ranges()
+                    .iter()
+                    .map(|range| (range.clone(), entry.offset())),
+            );
+            covered.insert(entry.source_range());
+        }
+        pieces.sort_by_key(|(range, _)| range.start);
+        pieces
     }
 
-    fn remove_and_shift(&mut self, shift_range: &Interval, offset: i64) -> Vec<Interval> {
-        let mut new_intervals = Vec::<Interval>::with_capacity(self.intervals.capacity());
-        let mut shifted_intervals = vec![];
-        for range in &self.intervals {
-            if shift_range.start <= range.start && range.end <= shift_range.end {
-                // shift entire range
-                shifted_intervals.push((range.start + offset)..(range.end + offset));
-            } else if shift_range.end <= range.start || range.end <= shift_range.start {
-                // keep range
-                new_intervals.push(range.clone());
-            } else if range.start < shift_range.start && shift_range.end < range.end {
-                // shift middle of range
-                new_intervals.push(range.start..shift_range.start);
-                shifted_intervals.push((shift_range.start + offset)..(shift_range.end + offset));
-                new_intervals.push(shift_range.end..range.end);
-            } else if range.start < shift_range.start {
-                // shift end of range
-                new_intervals.push(range.start..shift_range.start);
-                shifted_intervals.push((shift_range.start + offset)..(range.end + offset));
-            } else {
-                // shift start of range
-                assert!(shift_range.end < range.end);
-                shifted_intervals.push((range.start + offset)..(shift_range.end + offset));
-                new_intervals.push(shift_range.end..range.end);
+    /// Creates a mapping from pieces, dropping those that don't move
+    /// and merging adjacent pieces that move by the same offset.
+    fn from_pieces(source: &str, dest: &str, mut pieces: Vec<Piece>) -> Self {
+        pieces.retain(|(range, offset)| *offset != 0 && !range.is_empty());
+        pieces.sort_by_key(|(range, _)| range.start);
+        let mut entries = Vec::<MappingEntry>::new();
+        for (range, offset) in pieces {
+            if let Some(last) = entries.last_mut() {
+                if last.source_range().end == range.start && last.offset() == offset {
+                    last.range_length += range.end - range.start;
+                    continue;
+                }
             }
+            entries.push(MappingEntry {
+                dest_start: range.start + offset,
+                source_start: range.start,
+                range_length: range.end - range.start,
+            });
+        }
+        Self {
+            source: source.to_string(),
+            dest: dest.to_string(),
+            entries,
         }
-        self.intervals = new_intervals;
-        self.merge_overlapping();
-        shifted_intervals
     }
 
-    fn merge_overlapping(&mut self) {
-        self.intervals.sort_by(compare_interval);
-        let mut i = 0;
-        while i + 1 < self.intervals.len() {
-            let prev_range = &self.intervals[i];
-            let next_range = &self.intervals[i + 1];
-            if prev_range.start <= next_range.end && next_range.start <= prev_range.end {
-                let start = prev_range.start.min(next_range.start);
-                let end = prev_range.end.max(next_range.end);
-                self.intervals[i] = start..end;
-                self.intervals.remove(i + 1);
-            } else {
-                i += 1;
+    /// Composes this mapping with `next`, into a single mapping that does both in one step.
+    fn then(&self, next: &Mapping) -> Mapping {
+        // Values that are not moved by this mapping can still be moved by the next one,
+        // so fill the gaps between the pieces with pieces that don't move
+        let mut all_pieces = vec![];
+        let mut unmoved_start = i64::MIN;
+        for (range, offset) in self.pieces() {
+            if unmoved_start < range.start {
+                all_pieces.push((unmoved_start..range.start, 0));
             }
+            unmoved_start = range.end;
+            all_pieces.push((range, offset));
+        }
+        if unmoved_start < i64::MAX {
+            all_pieces.push((unmoved_start..i64::MAX, 0));
         }
+        let next_pieces = next.pieces();
+        let mut result = vec![];
+        for (range, offset) in all_pieces {
+            // Split where this piece lands at the sorted pieces of the next mapping
+            let dest = (range.start + offset)..(range.end + offset);
+            let first = next_pieces.partition_point(|(next_range, _)| next_range.end <= dest.start);
+            let mut start = dest.start;
+            for (next_range, next_offset) in &next_pieces[first..] {
+                if next_range.start >= dest.end {
+                    break;
+                }
+                if start < next_range.start {
+                    result.push(((start - offset)..(next_range.start - offset), offset));
+                    start = next_range.start;
+                }
+                let end = next_range.end.min(dest.end);
+                result.push(((start - offset)..(end - offset), offset + next_offset));
+                start = end;
+            }
+            if start < dest.end {
+                result.push(((start - offset)..(dest.end - offset), offset));
+            }
+        }
+        Mapping::from_pieces(&self.source, &next.dest, result)
     }
-}
 
-impl Mapping {
-    fn map_ranges(&self, ranges: IntervalSet) -> IntervalSet {
-        let mut remainder = ranges.clone();
-        let mut shifted = vec![];
-        for entry in &self.entries {
-            let range = entry.source_start..entry.source_start + entry.range_length;
-            let offset = entry.dest_start - entry.source_start;
-            shifted.extend_from_slice(&remainder.remove_and_shift(&range, offset));
+    /// The mapping that undoes this one, if every value has exactly one source.
+    #[allow(dead_code)]
+    fn inverse(&self) -> Option<Mapping> {
+        let pieces = self.pieces();
+        let sources = IntervalSet::from_ranges(pieces.iter().map(|(range, _)| range.clone()));
+        let dests = IntervalSet::from_ranges(
+            pieces
+                .iter()
+                .map(|(range, offset)| (range.start + offset)..(range.end + offset)),
+        );
+        // The moved values must be rearranged among themselves, without any overlap
+        if sources != dests {
+            return None;
         }
-        remainder.add_all(&shifted);
-        remainder
+        let inverse_pieces = pieces
+            .into_iter()
+            .map(|(range, offset)| ((range.start + offset)..(range.end + offset), -offset))
+            .collect();
+        Some(Mapping::from_pieces(
+            &self.dest,
+            &self.source,
+            inverse_pieces,
+        ))
     }
 }
 
 impl Almanac {
-    fn seed_to_loc_ranges(&self, seed: IntervalSet) -> IntervalSet {
-        let soil = self.seed_to_soil.map_ranges(seed);
-        let fert = self.soil_to_fert.map_ranges(soil);
-        let water = self.fert_to_water.map_ranges(fert);
-        let light = self.water_to_light.map_ranges(water);
-        let temp = self.light_to_temp.map_ranges(light);
-        let humid = self.temp_to_humid.map_ranges(temp);
-        self.humid_to_loc.map_ranges(humid)
+    fn mappings(&self) -> [&Mapping; 7] {
+        [
+            &self.seed_to_soil,
+            &self.soil_to_fert,
+            &self.fert_to_water,
+            &self.water_to_light,
+            &self.light_to_temp,
+            &self.temp_to_humid,
+            &self.humid_to_loc,
+        ]
     }
+
+    /// Composes all mappings into a single mapping from seed to location.
+    fn seed_to_loc(&self) -> Mapping {
+        let [first, rest @ ..] = self.mappings();
+        rest.into_iter()
+            .fold(first.clone(), |mapping, next| mapping.then(next))
+    }
+}
+
+#[aoc(day5, part1)]
+fn part1(input: &Almanac) -> i64 {
+    let seed_to_loc = input.seed_to_loc();
+    input
+        .seeds
+        .iter()
+        .map(|seed| seed_to_loc.map(*seed))
+        .min()
+        .unwrap()
 }
 
 #[aoc(day5, part2)]
 fn part2(input: &Almanac) -> i64 {
-    let mut seed = vec![];
-    for start_and_length in input.seeds.chunks_exact(2) {
-        if let [start, length] = start_and_length {
-            seed.push(*start..(start + length));
-        }
-    }
-    let seed = IntervalSet::from(seed);
-    let loc = input.seed_to_loc_ranges(seed);
-    loc.intervals.first().unwrap().start
+    let seed = input
+        .seeds
+        .chunks_exact(2)
+        .map(|pair| pair[0]..pair[0] + pair[1])
+        .collect();
+    let loc = input.seed_to_loc().map_ranges(seed);
+    loc.min().unwrap()
 }
 
 #[cfg(test)]
@@ -271,11 +362,53 @@ humidity-to-location map:
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 35);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 35);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 46);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 46);
+    }
+
+    #[test]
+    fn compose_mappings() {
+        let almanac = parse(INPUT).unwrap();
+        let seed_to_loc = almanac.seed_to_loc();
+        for seed in 0..110 {
+            let loc = almanac
+                .mappings()
+                .iter()
+                .fold(seed, |value, mapping| mapping.map(value));
+            assert_eq!(seed_to_loc.map(seed), loc, "seed {seed}");
+        }
+        assert!(seed_to_loc
+            .to_string()
+            .starts_with("seed-to-location map:\n"));
+        assert_eq!(seed_to_loc.to_string().parse(), Ok(seed_to_loc));
+    }
+
+    #[test]
+    fn overlapping_entries() {
+        // The first entry wins where entries overlap
+        let first = "a-to-b map:\n10 0 5\n20 2 5".parse::<Mapping>().unwrap();
+        assert_eq!(first.pieces(), vec![(0..5, 10), (5..7, 18)]);
+        let second = "b-to-c map:\n0 8 10\n100 0 3".parse::<Mapping>().unwrap();
+        let composed = first.then(&second);
+        for value in -5..30 {
+            assert_eq!(composed.map(value), second.map(first.map(value)), "{value}");
+        }
+    }
+
+    #[test]
+    fn inverse_mapping() {
+        let almanac = parse(INPUT).unwrap();
+        let loc_to_seed = almanac.seed_to_loc().inverse().unwrap();
+        assert_eq!(loc_to_seed.source, "location");
+        assert_eq!(loc_to_seed.map(35), 13);
+        assert_eq!(loc_to_seed.map(46), 82);
+        let mapping = "a-to-b map:\n10 0 5".parse::<Mapping>().unwrap();
+        assert_eq!(mapping.inverse(), None);
+        let mapping = "a-to-b map:\n5 0 5\n0 5 5".parse::<Mapping>().unwrap();
+        assert_eq!(mapping.inverse().unwrap().entries, mapping.entries);
     }
 }
@@ -1,75 +1,145 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+
 use aoc_runner_derive::aoc;
+use num_traits::CheckedMul;
+
+use crate::util::{isqrt, next_item, parse_number, strip_prefix, Num, ParseError};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Race<T = i64> {
+    time: T,
+    distance: T,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum RaceError {
+    Parse(ParseError),
+    /// The race is too long to compute its best distance.
+    Overflow,
+}
 
-struct Race {
-    time: u64,
-    distance: u64,
+impl Display for RaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RaceError::Parse(err) => write!(f, "{err}"),
+            RaceError::Overflow => write!(f, "race is too long"),
+        }
+    }
+}
+
+impl Error for RaceError {}
+
+impl From<ParseError> for RaceError {
+    fn from(err: ParseError) -> Self {
+        RaceError::Parse(err)
+    }
 }
 
-fn parse_part1(input: &str) -> Vec<Race> {
+fn split_lines(input: &str) -> Result<(&str, &str), ParseError> {
     let mut lines = input.lines();
-    let times = lines.next().unwrap().strip_prefix("Time:").unwrap();
-    let distances = lines.next().unwrap().strip_prefix("Distance:").unwrap();
-    let times = times
-        .split(' ')
-        .filter(|s| !s.is_empty())
-        .map(|x| x.parse().unwrap());
-    let distances = distances
-        .split(' ')
+    let times = strip_prefix(next_item(&mut lines, input, "times")?, "Time:")?;
+    let distances = strip_prefix(next_item(&mut lines, input, "distances")?, "Distance:")?;
+    Ok((times, distances))
+}
+
+/// Fails on negative numbers, since neither times nor distances can be negative.
+fn check_non_negative<T: Num>(s: &str, n: T) -> Result<T, ParseError> {
+    if n < T::zero() {
+        return Err(ParseError::expected(s, "non-negative number"));
+    }
+    Ok(n)
+}
+
+fn parse_numbers<T: Num + FromStr>(s: &str) -> Result<Vec<T>, ParseError> {
+    s.split(' ')
         .filter(|s| !s.is_empty())
-        .map(|x| x.parse().unwrap());
-    times
-        .zip(distances)
-        .map(|(time, distance)| Race { time, distance })
+        .map(|s| check_non_negative(s, parse_number(s)?))
         .collect()
 }
 
-impl Race {
-    fn simulate(&self, hold_time: u64) -> u64 {
-        let speed = hold_time;
-        let move_time = self.time.saturating_sub(hold_time);
-        speed * move_time
+fn parse_part1<T: Num + FromStr>(input: &str) -> Result<Vec<Race<T>>, ParseError> {
+    let (times, distances) = split_lines(input)?;
+    let times = parse_numbers(times)?;
+    let distances = parse_numbers(distances)?;
+    if times.len() != distances.len() {
+        return Err(ParseError::expected_after(
+            input,
+            format!("{} distances", times.len()),
+        ));
     }
+    Ok(times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, distance)| Race { time, distance })
+        .collect())
+}
 
-    fn ways_to_win(&self) -> u64 {
-        let mut ways = 0;
-        for hold_time in 1..self.time {
-            if self.simulate(hold_time) > self.distance {
-                ways += 1;
-            }
+impl<T: Num + CheckedMul> Race<T> {
+    /// The range of hold times that beat the record distance.
+    ///
+    /// Holding for `h` goes `h * (time - h)` far, so this solves `h * (time - h) > distance`
+    /// exactly, without floating point math. Returns `None` if the best possible distance
+    /// does not fit in `T`.
+    fn winning_hold_times(&self) -> Option<Range<T>> {
+        let two = T::one() + T::one();
+        // The best distance comes from holding for half the time
+        let half = self.time / two;
+        let best = half.checked_mul(&(self.time - half))?;
+        if best <= self.distance {
+            return Some(half..half);
         }
-        ways
+        // Holding `k` less than half goes `best - k * k - k * odd` far
+        let odd = self.time - half - half;
+        let margin = best - self.distance;
+        let mut k = isqrt(margin);
+        while k * k + k * odd >= margin {
+            k -= T::one();
+        }
+        let first = half - k;
+        // Holding for `h` goes just as far as holding for `time - h`
+        let last = self.time - first;
+        Some(first..last + T::one())
+    }
+
+    fn ways_to_win(&self) -> Option<T> {
+        let range = self.winning_hold_times()?;
+        Some(range.end - range.start)
     }
 }
 
 #[aoc(day6, part1)]
-fn part1(input: &str) -> u64 {
-    let races = parse_part1(input);
-    races.iter().map(|race| race.ways_to_win()).product()
+fn part1(input: &str) -> Result<i64, RaceError> {
+    let races: Vec<Race> = parse_part1(input).map_err(|err| err.locate(6, input))?;
+    races
+        .iter()
+        .map(|race| race.ways_to_win().ok_or(RaceError::Overflow))
+        .product()
 }
 
-fn parse_part2(input: &str) -> Race {
-    let mut lines = input.lines();
-    let time = lines.next().unwrap().strip_prefix("Time:").unwrap();
-    let distance = lines.next().unwrap().strip_prefix("Distance:").unwrap();
-    let time = time
-        .chars()
-        .filter(|&c| c != ' ')
-        .collect::<String>()
-        .parse()
-        .unwrap();
-    let distance = distance
+fn parse_kerned_number<T: Num + FromStr>(s: &str) -> Result<T, ParseError> {
+    let n = s
         .chars()
         .filter(|&c| c != ' ')
         .collect::<String>()
         .parse()
-        .unwrap();
-    Race { time, distance }
+        .map_err(|_| ParseError::expected(s.trim_start(), "number"))?;
+    check_non_negative(s.trim_start(), n)
+}
+
+fn parse_part2<T: Num + FromStr>(input: &str) -> Result<Race<T>, ParseError> {
+    let (time, distance) = split_lines(input)?;
+    let time = parse_kerned_number(time)?;
+    let distance = parse_kerned_number(distance)?;
+    Ok(Race { time, distance })
 }
 
 #[aoc(day6, part2)]
-fn part2(input: &str) -> u64 {
-    let race = parse_part2(input);
-    race.ways_to_win()
+fn part2(input: &str) -> Result<i128, RaceError> {
+    let race: Race<i128> = parse_part2(input).map_err(|err| err.locate(6, input))?;
+    race.ways_to_win().ok_or(RaceError::Overflow)
 }
 
 #[cfg(test)]
@@ -81,11 +151,62 @@ Distance:  9  40  200";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(INPUT), 288);
+        assert_eq!(part1(INPUT), Ok(288));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(INPUT), 71503);
+        assert_eq!(part2(INPUT), Ok(71503));
+    }
+
+    /// Counts the winning hold times by trying all of them.
+    fn ways_to_win_slow(race: &Race) -> i64 {
+        (0..=race.time)
+            .filter(|hold| hold * (race.time - hold) > race.distance)
+            .count() as i64
+    }
+
+    #[test]
+    fn winning_hold_times() {
+        let race = Race {
+            time: 30,
+            distance: 200,
+        };
+        assert_eq!(race.winning_hold_times(), Some(11..20));
+        for time in 0..40 {
+            for distance in 0..time * time / 4 + 3 {
+                let race = Race { time, distance };
+                assert_eq!(
+                    race.ways_to_win(),
+                    Some(ways_to_win_slow(&race)),
+                    "{race:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn large_races() {
+        let race = Race::<i128> {
+            time: 1 << 64,
+            distance: (1 << 126) - 1,
+        };
+        assert_eq!(race.winning_hold_times(), Some((1 << 63)..(1 << 63) + 1));
+        let race = Race::<i64> {
+            time: i64::MAX,
+            distance: 0,
+        };
+        assert_eq!(race.winning_hold_times(), None);
+        let race = Race::<i128> {
+            time: u64::MAX.into(),
+            distance: 0,
+        };
+        assert_eq!(race.ways_to_win(), Some(i128::from(u64::MAX) - 1));
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert!(part1("Time: 7 -15\nDistance: 9 40").is_err());
+        assert!(part2("Time: 7 15\nDistance: -9 40").is_err());
     }
 }
@@ -6,12 +6,17 @@ use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::util::{next_item, parse_lines, parse_number, split_once, ParseError};
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 struct Card(char);
 
 impl Card {
-    fn parse(c: char) -> Card {
-        Card(c)
+    fn parse(c: char) -> Option<Card> {
+        match c {
+            'A' | 'K' | 'Q' | 'J' | 'T' | '2'..='9' => Some(Card(c)),
+            _ => None,
+        }
     }
 }
 
@@ -31,11 +36,18 @@ impl Debug for Card {
 struct Hand([Card; 5]);
 
 impl FromStr for Hand {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let cards = s.chars().take(5).map(Card::parse).collect::<Vec<_>>();
-        let cards = cards.try_into().unwrap();
+        let mut cards = [Card('2'); 5];
+        let mut chars = s.char_indices();
+        for card in cards.iter_mut() {
+            let (i, c) = next_item(&mut chars, s, "card")?;
+            *card = Card::parse(c).ok_or_else(|| ParseError::expected(&s[i..], "card"))?;
+        }
+        if let Some((i, _)) = chars.next() {
+            return Err(ParseError::expected(&s[i..], "end of hand"));
+        }
         Ok(Self(cards))
     }
 }
@@ -62,19 +74,19 @@ struct Bid {
 }
 
 impl FromStr for Bid {
-    type Err = ();
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (hand, amount) = s.split_once(' ').unwrap();
-        let hand = hand.parse().unwrap();
-        let amount = amount.parse().unwrap();
+        let (hand, amount) = split_once(s, " ")?;
+        let hand = hand.parse()?;
+        let amount = parse_number(amount)?;
         Ok(Self { hand, amount })
     }
 }
 
 #[aoc_generator(day7)]
-fn parse(input: &str) -> Vec<Bid> {
-    input.lines().map(|line| line.parse().unwrap()).collect()
+fn parse(input: &str) -> Result<Vec<Bid>, ParseError> {
+    parse_lines(input).map_err(|err| err.locate(7, input))
 }
 
 impl Card {
@@ -202,11 +214,11 @@ QQQJA 483";
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 6440);
+        assert_eq!(part1(&parse(INPUT).unwrap()), 6440);
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 5905);
+        assert_eq!(part2(&parse(INPUT).unwrap()), 5905);
     }
 }
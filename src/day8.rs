@@ -1,8 +1,10 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::util::lcm;
+use crate::util::{crt, find_cycle_brent, split_once, strip_prefix, strip_suffix, ParseError};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Direction {
@@ -10,72 +12,242 @@ enum Direction {
     Right,
 }
 
+/// A node and the indices of the nodes it leads to.
 struct Node {
-    left: String,
-    right: String,
+    label: String,
+    left: usize,
+    right: usize,
 }
 
 struct Input {
     steps: Vec<Direction>,
-    nodes: HashMap<String, Node>,
+    nodes: Vec<Node>,
 }
 
 #[aoc_generator(day8)]
-fn parse(input: &str) -> Input {
-    let (steps, nodes) = input.split_once("\n\n").unwrap();
+fn parse(input: &str) -> Result<Input, ParseError> {
+    parse_input(input).map_err(|err| err.locate(8, input))
+}
+
+fn parse_input(input: &str) -> Result<Input, ParseError> {
+    let (steps, nodes) = split_once(input, "\n\n")?;
+    if steps.is_empty() {
+        return Err(ParseError::expected(steps, "L or R"));
+    }
     let steps = steps
-        .chars()
-        .map(|c| match c {
-            'L' => Direction::Left,
-            'R' => Direction::Right,
-            c => panic!("invalid direction {c}"),
+        .char_indices()
+        .map(|(i, c)| match c {
+            'L' => Ok(Direction::Left),
+            'R' => Ok(Direction::Right),
+            _ => Err(ParseError::expected(&steps[i..], "L or R")),
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
     let nodes = nodes
         .lines()
         .map(|line| {
-            let (label, next) = line.split_once(" = ").unwrap();
-            let next = next.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
-            let (left, right) = next.split_once(", ").unwrap();
-            let node = Node {
-                left: left.to_string(),
-                right: right.to_string(),
-            };
-            (label.to_string(), node)
+            let (label, next) = split_once(line, " = ")?;
+            let next = strip_suffix(strip_prefix(next, "(")?, ")")?;
+            let (left, right) = split_once(next, ", ")?;
+            Ok((label, left, right))
         })
-        .collect();
-    Input { steps, nodes }
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    // Every node must lead to nodes that exist, so walking never gets stuck
+    let indices = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, &(label, _, _))| (label, i))
+        .collect::<HashMap<_, _>>();
+    let index = |label: &str| {
+        indices
+            .get(label)
+            .copied()
+            .ok_or_else(|| ParseError::expected(label, "label of a node"))
+    };
+    let nodes = nodes
+        .iter()
+        .map(|&(label, left, right)| {
+            Ok(Node {
+                label: label.to_string(),
+                left: index(left)?,
+                right: index(right)?,
+            })
+        })
+        .collect::<Result<_, ParseError>>()?;
+    Ok(Input { steps, nodes })
 }
 
-fn solve(start: &str, is_goal: impl Fn(&str) -> bool, input: &Input) -> i64 {
+fn solve(start: usize, is_goal: impl Fn(&str) -> bool, input: &Input) -> i64 {
     let mut current = start;
-    let mut steps = input.steps.iter().cycle();
+    let mut step_index = 0;
     let mut step_count = 0;
-    while !is_goal(current) {
-        let current_node = input.nodes.get(current).unwrap();
-        let step = steps.next().unwrap();
-        current = match step {
-            Direction::Left => &current_node.left,
-            Direction::Right => &current_node.right,
-        };
+    while !is_goal(&input.nodes[current].label) {
+        current = input.next(current, step_index);
+        step_index = (step_index + 1) % input.steps.len();
         step_count += 1
     }
     step_count
 }
 
 #[aoc(day8, part1)]
-fn part1(input: &Input) -> i64 {
-    solve("AAA", |label| label == "ZZZ", input)
+fn part1(input: &Input) -> Result<i64, WalkError> {
+    let start = input
+        .nodes
+        .iter()
+        .position(|node| node.label == "AAA")
+        .ok_or(WalkError::NoStart)?;
+    Ok(solve(start, |label| label == "ZZZ", input))
+}
+
+/// All times at which a ghost stands on a goal node.
+///
+/// The ghost's state is its node together with its position in the instructions,
+/// so after at most (nodes * instructions) steps it must start going round in a cycle.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct GhostWalk {
+    /// Goal times before the cycle starts.
+    prefix_goals: Vec<i64>,
+    cycle_start: i64,
+    cycle_length: i64,
+    /// Goal times during the first pass through the cycle.
+    /// The ghost is also on a goal at each of these times plus any multiple of the cycle length.
+    cycle_goals: Vec<i64>,
+}
+
+/// The most combinations of cycle goals to consider when aligning the ghosts.
+const MAX_ALIGNMENTS: usize = 1_000_000;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum WalkError {
+    /// There is no node "AAA" to start from.
+    NoStart,
+    /// The ghosts never all stand on a goal node at the same time.
+    Never,
+    /// The ghosts' cycle goals line up in too many different ways.
+    TooManyAlignments { limit: usize },
+}
+
+impl Display for WalkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalkError::NoStart => write!(f, "there is no node AAA"),
+            WalkError::Never => write!(
+                f,
+                "the ghosts never all stand on a goal node at the same time"
+            ),
+            WalkError::TooManyAlignments { limit } => write!(
+                f,
+                "the ghosts' cycle goals can align in more than {limit} ways"
+            ),
+        }
+    }
+}
+
+impl Error for WalkError {}
+
+impl Input {
+    /// The node reached from `node` by following step `step_index` of the instructions.
+    fn next(&self, node: usize, step_index: usize) -> usize {
+        let node = &self.nodes[node];
+        match self.steps[step_index] {
+            Direction::Left => node.left,
+            Direction::Right => node.right,
+        }
+    }
+
+    fn walk_ghost(&self, start: usize, is_goal: impl Fn(&str) -> bool) -> GhostWalk {
+        let step = |&(node, step_index): &(usize, usize)| {
+            (
+                self.next(node, step_index),
+                (step_index + 1) % self.steps.len(),
+            )
+        };
+        let cycle = find_cycle_brent((start, 0), step);
+        let mut prefix_goals = Vec::new();
+        let mut cycle_goals = Vec::new();
+        let mut state = (start, 0);
+        for time in 0..cycle.start + cycle.length {
+            if is_goal(&self.nodes[state.0].label) {
+                if time < cycle.start {
+                    prefix_goals.push(time as i64);
+                } else {
+                    cycle_goals.push(time as i64);
+                }
+            }
+            state = step(&state);
+        }
+        GhostWalk {
+            prefix_goals,
+            cycle_start: cycle.start as i64,
+            cycle_length: cycle.length as i64,
+            cycle_goals,
+        }
+    }
+}
+
+impl GhostWalk {
+    fn is_goal_at(&self, time: i64) -> bool {
+        if time < self.cycle_start {
+            self.prefix_goals.contains(&time)
+        } else {
+            let time = self.cycle_start + (time - self.cycle_start) % self.cycle_length;
+            self.cycle_goals.contains(&time)
+        }
+    }
+}
+
+/// Finds the first time at which every ghost stands on a goal.
+fn first_arrival(walks: &[GhostWalk]) -> Result<i64, WalkError> {
+    // Arrivals where some ghost hasn't reached its cycle yet, must be one of that ghost's prefix goals
+    let prefix_arrival = walks
+        .iter()
+        .flat_map(|walk| walk.prefix_goals.iter().copied())
+        .filter(|&time| walks.iter().all(|walk| walk.is_goal_at(time)))
+        .min();
+    // Otherwise, every ghost is at one of its cycle goals. Add the ghosts one by one,
+    // combining the times at which they are all on a goal using the CRT.
+    // The combined modulus is the same for every residue, so equal residues can be merged.
+    let mut residues = vec![0];
+    let mut modulus = 1;
+    for walk in walks {
+        let mut next_residues = vec![];
+        for &residue in &residues {
+            for &goal in &walk.cycle_goals {
+                if let Some((next_residue, next_modulus)) =
+                    crt([(residue, modulus), (goal, walk.cycle_length)])
+                {
+                    next_residues.push(next_residue);
+                    modulus = next_modulus;
+                }
+            }
+        }
+        next_residues.sort_unstable();
+        next_residues.dedup();
+        if next_residues.len() > MAX_ALIGNMENTS {
+            return Err(WalkError::TooManyAlignments {
+                limit: MAX_ALIGNMENTS,
+            });
+        }
+        residues = next_residues;
+    }
+    // Skip ahead to the first such time after all ghosts have entered their cycles
+    let earliest = walks.iter().map(|walk| walk.cycle_start).max().unwrap_or(0);
+    let cycle_arrival = residues
+        .into_iter()
+        .map(|residue| residue + (earliest - residue + modulus - 1).div_euclid(modulus) * modulus)
+        .min();
+    match (prefix_arrival, cycle_arrival) {
+        (Some(left), Some(right)) => Ok(left.min(right)),
+        (left, right) => left.or(right).ok_or(WalkError::Never),
+    }
 }
 
 #[aoc(day8, part2)]
-fn part2(input: &Input) -> i64 {
-    input
-        .nodes
-        .keys()
-        .filter(|label| label.ends_with('A'))
-        .map(|start| solve(start, |label| label.ends_with('Z'), input))
-        .fold(1, lcm)
+fn part2(input: &Input) -> Result<i64, WalkError> {
+    let walks = (0..input.nodes.len())
+        .filter(|&node| input.nodes[node].label.ends_with('A'))
+        .map(|start| input.walk_ghost(start, |label| label.ends_with('Z')))
+        .collect::<Vec<_>>();
+    first_arrival(&walks)
 }
 
 #[cfg(test)]
@@ -89,7 +261,7 @@ mod tests {
 AAA = (BBB, BBB)
 BBB = (AAA, ZZZ)
 ZZZ = (ZZZ, ZZZ)";
-        assert_eq!(part1(&parse(input)), 6);
+        assert_eq!(part1(&parse(input).unwrap()), Ok(6));
     }
 
     #[test]
@@ -104,6 +276,88 @@ ZZZ = (ZZZ, ZZZ)";
 22C = (22Z, 22Z)
 22Z = (22B, 22B)
 XXX = (XXX, XXX)";
-        assert_eq!(part2(&parse(input)), 6);
+        assert_eq!(part2(&parse(input).unwrap()), Ok(6));
+    }
+
+    #[test]
+    fn part2_offset_cycles() {
+        // Ghost 1 reaches its goal at times 2, 5, 8, ...
+        // Ghost 2 reaches its goals at times 1, 3, 5, 7, ...
+        let input = "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (11D, 11D)
+11D = (11Z, 11Z)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22Z, 22Z)";
+        let input = parse(input).unwrap();
+        let walk = input.walk_ghost(0, |label| label.ends_with('Z'));
+        assert_eq!(
+            walk,
+            GhostWalk {
+                prefix_goals: vec![],
+                cycle_start: 2,
+                cycle_length: 3,
+                cycle_goals: vec![2],
+            }
+        );
+        assert_eq!(part2(&input), Ok(5));
+    }
+
+    #[test]
+    fn part2_prefix_goal() {
+        // Ghost 1 only reaches its goal at time 1, and ghost 2 at all times after that
+        let input = "L
+
+11A = (11Z, 11Z)
+11Z = (11B, 11B)
+11B = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22Z, 22Z)";
+        assert_eq!(part2(&parse(input).unwrap()), Ok(1));
+    }
+
+    #[test]
+    fn part2_no_arrival() {
+        // Ghost 1 is on a goal at odd times, ghost 2 at even times
+        let input = "L
+
+11A = (11Z, 11Z)
+11Z = (11A, 11A)
+22A = (22B, 22B)
+22B = (22Z, 22Z)
+22Z = (22B, 22B)";
+        assert_eq!(part2(&parse(input).unwrap()), Err(WalkError::Never));
+    }
+
+    #[test]
+    fn part2_many_goals() {
+        // Each ghost is on a goal three times per cycle, so trying every combination
+        // of goals would take 3^20 steps.
+        let mut input = "L\n".to_string();
+        for ghost in 0..20 {
+            input += &format!(
+                "
+{ghost}A = ({ghost}BZ, {ghost}BZ)
+{ghost}BZ = ({ghost}CZ, {ghost}CZ)
+{ghost}CZ = ({ghost}DZ, {ghost}DZ)
+{ghost}DZ = ({ghost}E, {ghost}E)
+{ghost}E = ({ghost}BZ, {ghost}BZ)"
+            );
+        }
+        assert_eq!(part2(&parse(&input).unwrap()), Ok(1));
+    }
+
+    #[test]
+    fn parse_missing_node() {
+        let input = "L
+
+AAA = (BBB, ZZZ)
+ZZZ = (ZZZ, ZZZ)";
+        let err = parse(input).err().unwrap();
+        assert_eq!((err.line, err.column), (Some(3), Some(8)));
     }
 }
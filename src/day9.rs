@@ -1,41 +1,145 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
 use aoc_runner_derive::{aoc, aoc_generator};
+use num_traits::{One, Zero};
 
-use crate::util::array_windows;
+use crate::util::{array_windows, gcd, parse_number, ParseError, Ratio};
 
 #[aoc_generator(day9)]
-fn parse(input: &str) -> Vec<Vec<i64>> {
+fn parse(input: &str) -> Result<Vec<Vec<i64>>, ParseError> {
     input
         .lines()
-        .map(|line| line.split(' ').map(|x| x.parse().unwrap()).collect())
-        .collect()
+        .map(|line| line.split(' ').map(parse_number).collect())
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.locate(9, input))
+}
+
+/// A polynomial through a sequence, in Newton's forward difference form:
+/// the value at index `n` is the sum of `differences[k] * binomial(n, k)`.
+///
+/// See: https://en.wikipedia.org/wiki/Newton_polynomial#Newton_forward_divided_difference_formula
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Polynomial {
+    /// The first value of each row in the difference table, without trailing zeros.
+    differences: Vec<i128>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PredictionError {
+    /// The sequence is too short to confirm it is a polynomial.
+    NotPolynomial,
+    /// A difference or the predicted value doesn't fit in an i128.
+    Overflow,
 }
 
-fn predict(sequence: &[i64]) -> i64 {
-    if sequence.iter().all(|&x| x == 0) {
-        return 0;
+impl Display for PredictionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PredictionError::NotPolynomial => {
+                write!(f, "sequence is too short to confirm it is a polynomial")
+            }
+            PredictionError::Overflow => write!(f, "sequence values are too large"),
+        }
     }
-    let differences = array_windows(sequence)
-        .map(|[a, b]| b - a)
-        .collect::<Vec<_>>();
-    return sequence.last().unwrap() + predict(&differences);
 }
 
-#[aoc(day9, part1)]
-fn part1(input: &[Vec<i64>]) -> i64 {
-    input.iter().map(|sequence| predict(sequence)).sum()
+impl Error for PredictionError {}
+
+impl Polynomial {
+    /// Finds the polynomial through the sequence, where the first value has index 0.
+    ///
+    /// Fails unless the difference table ends in a row of zeros,
+    /// since then any higher degree polynomial would fit just as well.
+    fn fit(sequence: &[i64]) -> Result<Self, PredictionError> {
+        let mut differences = vec![];
+        let mut row = sequence.iter().map(|&x| i128::from(x)).collect::<Vec<_>>();
+        while row.iter().any(|&x| x != 0) {
+            differences.push(row[0]);
+            row = array_windows(&row)
+                .map(|[a, b]| b.checked_sub(*a).ok_or(PredictionError::Overflow))
+                .collect::<Result<_, _>>()?;
+        }
+        if row.is_empty() {
+            return Err(PredictionError::NotPolynomial);
+        }
+        Ok(Self { differences })
+    }
+
+    /// The degree of the polynomial, or `None` for the zero polynomial.
+    #[allow(dead_code)]
+    fn degree(&self) -> Option<usize> {
+        self.differences.len().checked_sub(1)
+    }
+
+    /// Evaluates the polynomial at any index, which may also be negative.
+    ///
+    /// Returns `None` if the value, or one of its terms, doesn't fit in an i128.
+    fn evaluate(&self, index: i64) -> Option<i128> {
+        let index = i128::from(index);
+        let mut binomial = 1i128;
+        let mut value = 0i128;
+        for (k, &difference) in (0..).zip(&self.differences) {
+            if k > 0 {
+                // binomial(n, k) = binomial(n, k - 1) * (n - k + 1) / k, which divides exactly,
+                // so k / divisor divides n - k + 1
+                let divisor = gcd(binomial, k);
+                binomial = (binomial / divisor).checked_mul((index - k + 1) / (k / divisor))?;
+            }
+            value = value.checked_add(difference.checked_mul(binomial)?)?;
+        }
+        Some(value)
+    }
+
+    /// The coefficients of the polynomial, starting from the constant term.
+    #[allow(dead_code)]
+    fn coefficients(&self) -> Vec<Ratio> {
+        let mut coefficients = vec![Ratio::zero(); self.differences.len()];
+        // The coefficients of binomial(x, k) = x * (x - 1) * ... * (x - k + 1) / k!
+        let mut binomial = vec![Ratio::one()];
+        for (k, &difference) in (0i64..).zip(&self.differences) {
+            for (coefficient, &term) in coefficients.iter_mut().zip(&binomial) {
+                *coefficient += term * Ratio::from(difference);
+            }
+            let mut next = vec![Ratio::zero(); binomial.len() + 1];
+            for (power, &term) in binomial.iter().enumerate() {
+                next[power + 1] += term / Ratio::from(k + 1);
+                next[power] -= term * Ratio::from(k) / Ratio::from(k + 1);
+            }
+            binomial = next;
+        }
+        coefficients
+    }
 }
 
-#[aoc(day9, part2)]
-fn part2(input: &[Vec<i64>]) -> i64 {
-    let reversed_inputs = input
+fn fit_all(input: &[Vec<i64>]) -> Result<Vec<(Polynomial, i64)>, PredictionError> {
+    input
+        .iter()
+        .map(|sequence| Ok((Polynomial::fit(sequence)?, sequence.len() as i64)))
+        .collect()
+}
+
+/// Sums the values of all polynomials at the given index.
+fn sum_at(
+    polynomials: &[(Polynomial, i64)],
+    index: impl Fn(i64) -> i64,
+) -> Result<i128, PredictionError> {
+    polynomials
         .iter()
-        .cloned()
-        .map(|mut sequence| {
-            sequence.reverse();
-            sequence
+        .try_fold(0i128, |sum, (polynomial, len)| {
+            sum.checked_add(polynomial.evaluate(index(*len))?)
         })
-        .collect::<Vec<_>>();
-    part1(&reversed_inputs)
+        .ok_or(PredictionError::Overflow)
+}
+
+#[aoc(day9, part1)]
+fn part1(input: &[Vec<i64>]) -> Result<i128, PredictionError> {
+    sum_at(&fit_all(input)?, |len| len)
+}
+
+#[aoc(day9, part2)]
+fn part2(input: &[Vec<i64>]) -> Result<i128, PredictionError> {
+    sum_at(&fit_all(input)?, |_| -1)
 }
 
 #[cfg(test)]
@@ -48,11 +152,77 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 114);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(114));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 2);
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(2));
+    }
+
+    #[test]
+    fn fit_polynomial() {
+        // 10 13 16 21 30 45 = x^3/3 - x^2 + 11x/3 + 10
+        let polynomial = Polynomial::fit(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(polynomial.degree(), Some(3));
+        assert_eq!(
+            polynomial.coefficients(),
+            vec![
+                Ratio::from(10),
+                Ratio::new(11, 3),
+                Ratio::from(-1),
+                Ratio::new(1, 3)
+            ]
+        );
+        assert_eq!(polynomial.evaluate(6), Some(68));
+        assert_eq!(polynomial.evaluate(-1), Some(5));
+        assert_eq!(polynomial.evaluate(-3), Some(-19));
+        assert_eq!(
+            polynomial.evaluate(1_000_000),
+            Some(333_332_333_337_000_010)
+        );
+    }
+
+    #[test]
+    fn evaluate_far_away() {
+        let cube = Polynomial::fit(&[0, 1, 8, 27, 64]).unwrap();
+        let trillion = 1_000_000_000_000;
+        assert_eq!(cube.evaluate(trillion), Some(i128::from(trillion).pow(3)));
+        assert_eq!(cube.evaluate(-trillion), Some(-i128::from(trillion).pow(3)));
+        let fourth = Polynomial::fit(&[0, 1, 16, 81, 256, 625]).unwrap();
+        assert_eq!(fourth.evaluate(trillion), None);
+        assert_eq!(
+            part1(&[vec![0, 1, 16, 81, 256, 625], vec![i64::MAX; 2]]),
+            Ok(1296 + i128::from(i64::MAX))
+        );
+    }
+
+    #[test]
+    fn fit_constant() {
+        let polynomial = Polynomial::fit(&[7, 7]).unwrap();
+        assert_eq!(polynomial.degree(), Some(0));
+        assert_eq!(polynomial.evaluate(-100), Some(7));
+        assert_eq!(Polynomial::fit(&[0, 0, 0]).unwrap().degree(), None);
+    }
+
+    #[test]
+    fn fit_not_polynomial() {
+        let not_polynomial = Err(PredictionError::NotPolynomial);
+        assert_eq!(Polynomial::fit(&[1, 2, 4, 8, 16]), not_polynomial);
+        assert_eq!(Polynomial::fit(&[5]), not_polynomial);
+        assert_eq!(Polynomial::fit(&[]), not_polynomial);
+    }
+
+    #[test]
+    fn fit_extreme_values() {
+        // The differences don't fit in an i64
+        let polynomial = Polynomial::fit(&[i64::MIN, i64::MAX, i64::MAX, i64::MIN]).unwrap();
+        assert_eq!(polynomial.degree(), Some(2));
+        assert_eq!(polynomial.evaluate(4), Some(5 * i128::from(i64::MIN) + 2));
+        // Alternating extremes double the differences on every row
+        let sequence = (0..130)
+            .map(|i| if i % 2 == 0 { i64::MIN } else { i64::MAX })
+            .collect::<Vec<_>>();
+        assert_eq!(Polynomial::fit(&sequence), Err(PredictionError::Overflow));
     }
 }
@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+
+use super::{Num, Vector};
+
+/// An axis-aligned box, containing every point `p` with `min <= p < max` along each axis.
+///
+/// A cuboid where `max <= min` along any axis is empty.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Cuboid<const N: usize, T: Num = i32> {
+    pub min: Vector<N, T>,
+    pub max: Vector<N, T>,
+}
+
+#[inline]
+fn min<T: Num>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+#[inline]
+fn max<T: Num>(a: T, b: T) -> T {
+    if a < b {
+        b
+    } else {
+        a
+    }
+}
+
+#[allow(dead_code)]
+impl<const N: usize, T: Num> Cuboid<N, T> {
+    pub fn new(min: Vector<N, T>, max: Vector<N, T>) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates a cuboid from its lowest and highest corner, both included.
+    pub fn from_inclusive(min: Vector<N, T>, max: Vector<N, T>) -> Self {
+        Self {
+            min,
+            max: max.map(|x| x + T::one()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..N).any(|axis| self.max.coords[axis] <= self.min.coords[axis])
+    }
+
+    /// The length of the cuboid along `axis`.
+    pub fn extent(&self, axis: usize) -> T {
+        max(self.max.coords[axis] - self.min.coords[axis], T::zero())
+    }
+
+    pub fn volume(&self) -> T {
+        let mut volume = T::one();
+        for axis in 0..N {
+            volume *= self.extent(axis);
+        }
+        volume
+    }
+
+    pub fn contains(&self, point: Vector<N, T>) -> bool {
+        (0..N).all(|axis| {
+            self.min.coords[axis] <= point.coords[axis]
+                && point.coords[axis] < self.max.coords[axis]
+        })
+    }
+
+    pub fn contains_cuboid(&self, other: &Self) -> bool {
+        other.is_empty()
+            || (0..N).all(|axis| {
+                self.min.coords[axis] <= other.min.coords[axis]
+                    && other.max.coords[axis] <= self.max.coords[axis]
+            })
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let result = Self {
+            min: self.min.zip_with(&other.min, max),
+            max: self.max.zip_with(&other.max, min),
+        };
+        (!result.is_empty()).then_some(result)
+    }
+
+    /// Splits into two halves along `axis`.
+    /// The first half contains everything below `value`,
+    /// the second half everything starting from (and including) `value`.
+    /// Either half may be empty.
+    pub fn split(&self, axis: usize, value: T) -> [Self; 2] {
+        let value = min(max(value, self.min.coords[axis]), self.max.coords[axis]);
+        let mut below = *self;
+        let mut above = *self;
+        below.max.coords[axis] = value;
+        above.min.coords[axis] = value;
+        [below, above]
+    }
+
+    /// Removes `other` from this cuboid, leaving at most `2 * N` disjoint cuboids.
+    pub fn subtract(&self, other: &Self) -> Vec<Self> {
+        let Some(overlap) = self.intersection(other) else {
+            return if self.is_empty() { vec![] } else { vec![*self] };
+        };
+        let mut result = Vec::with_capacity(2 * N);
+        let mut rest = *self;
+        for axis in 0..N {
+            let [below, middle] = rest.split(axis, overlap.min.coords[axis]);
+            let [middle, above] = middle.split(axis, overlap.max.coords[axis]);
+            result.extend([below, above].into_iter().filter(|c| !c.is_empty()));
+            rest = middle;
+        }
+        result
+    }
+
+    pub fn translate(&self, offset: Vector<N, T>) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+impl<const N: usize, T: Num> Display for Cuboid<N, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Vector2D;
+
+    fn rect(min: (i32, i32), max: (i32, i32)) -> Cuboid<2> {
+        Cuboid::new(Vector2D::new(min.0, min.1), Vector2D::new(max.0, max.1))
+    }
+
+    #[test]
+    fn test_volume() {
+        assert_eq!(rect((0, 0), (3, 4)).volume(), 12);
+        assert_eq!(rect((0, 0), (-3, 4)).volume(), 0);
+        assert!(rect((1, 1), (1, 4)).is_empty());
+        let cube = Cuboid::from_inclusive(Vector::from([1, 1, 1]), Vector::from([2, 2, 2]));
+        assert_eq!(cube.volume(), 8);
+    }
+
+    #[test]
+    fn test_contains() {
+        let a = rect((0, 0), (3, 4));
+        assert!(a.contains(Vector2D::new(0, 0)));
+        assert!(a.contains(Vector2D::new(2, 3)));
+        assert!(!a.contains(Vector2D::new(3, 3)));
+        assert!(a.contains_cuboid(&rect((1, 1), (3, 4))));
+        assert!(!a.contains_cuboid(&rect((1, 1), (4, 4))));
+    }
+
+    #[test]
+    fn test_intersection() {
+        let a = rect((0, 0), (3, 4));
+        assert_eq!(
+            a.intersection(&rect((2, -1), (5, 2))),
+            Some(rect((2, 0), (3, 2)))
+        );
+        assert_eq!(a.intersection(&rect((3, 0), (5, 2))), None);
+        assert!(!a.intersects(&rect((3, 0), (5, 2))));
+    }
+
+    #[test]
+    fn test_split() {
+        let a = rect((0, 0), (3, 4));
+        assert_eq!(a.split(1, 1), [rect((0, 0), (3, 1)), rect((0, 1), (3, 4))]);
+        let [below, above] = a.split(0, 5);
+        assert_eq!(below, a);
+        assert!(above.is_empty());
+    }
+
+    #[test]
+    fn test_subtract() {
+        let a = rect((0, 0), (4, 4));
+        let hole = rect((1, 1), (2, 3));
+        let parts = a.subtract(&hole);
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts.iter().map(Cuboid::volume).sum::<i32>(), 14);
+        for (i, part) in parts.iter().enumerate() {
+            assert!(!part.intersects(&hole));
+            assert!(parts[i + 1..].iter().all(|other| !part.intersects(other)));
+        }
+        assert_eq!(a.subtract(&rect((5, 5), (6, 6))), vec![a]);
+        assert_eq!(a.subtract(&rect((-1, -1), (5, 5))), vec![]);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A cycle in a sequence of states.
+///
+/// The first `start` states are visited once, after which every `length` states repeat forever.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+#[allow(dead_code)]
+impl Cycle {
+    /// Finds the earliest step that has the same state as the given step.
+    pub fn normalize(&self, step: usize) -> usize {
+        if step < self.start {
+            step
+        } else {
+            self.start + (step - self.start) % self.length
+        }
+    }
+
+    /// Finds the state after `n` steps, simulating no further than the end of the first cycle.
+    pub fn state_at<S>(&self, initial: S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+        let mut state = initial;
+        for _ in 0..self.normalize(n) {
+            state = step(&state);
+        }
+        state
+    }
+}
+
+/// Finds the cycle reached by repeatedly applying `step` to `initial`,
+/// by remembering every state in a hash map.
+#[allow(dead_code)]
+pub fn find_cycle<S: Clone + Eq + Hash>(initial: S, mut step: impl FnMut(&S) -> S) -> Cycle {
+    let mut seen = HashMap::new();
+    let mut state = initial;
+    loop {
+        let time = seen.len();
+        if let Some(&start) = seen.get(&state) {
+            return Cycle {
+                start,
+                length: time - start,
+            };
+        }
+        let next = step(&state);
+        seen.insert(state, time);
+        state = next;
+    }
+}
+
+/// Finds the cycle reached by repeatedly applying `step` to `initial`,
+/// using Brent's algorithm. This only keeps two states in memory at a time,
+/// but needs to run the steps of the cycle a few times.
+///
+/// See: https://en.wikipedia.org/wiki/Cycle_detection#Brent's_algorithm
+#[allow(dead_code)]
+pub fn find_cycle_brent<S: Clone + Eq>(initial: S, mut step: impl FnMut(&S) -> S) -> Cycle {
+    // Find the cycle length by moving the tortoise to the hare at every power of two.
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+    // Find the cycle start by keeping the hare one cycle length ahead of the tortoise.
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    Cycle { start, length }
+}
+
+/// Finds the state after `n` applications of `step` to `initial`,
+/// skipping over all repetitions once a cycle is found.
+pub fn state_at<S: Clone + Eq + Hash>(initial: S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+    let mut seen = HashMap::new();
+    let mut history = Vec::new();
+    let mut state = initial;
+    while history.len() < n {
+        if let Some(&start) = seen.get(&state) {
+            let cycle = Cycle {
+                start,
+                length: history.len() - start,
+            };
+            return history.swap_remove(cycle.normalize(n));
+        }
+        seen.insert(state.clone(), history.len());
+        let next = step(&state);
+        history.push(state);
+        state = next;
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0, 1, 2, 3, 4, 2, 3, 4, 2, ...
+    fn step(&x: &u32) -> u32 {
+        if x < 4 {
+            x + 1
+        } else {
+            2
+        }
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let expected = Cycle {
+            start: 2,
+            length: 3,
+        };
+        assert_eq!(find_cycle(0, step), expected);
+        assert_eq!(find_cycle_brent(0, step), expected);
+    }
+
+    #[test]
+    fn test_find_cycle_immediate() {
+        let expected = Cycle {
+            start: 0,
+            length: 1,
+        };
+        assert_eq!(find_cycle(7, |&x| x), expected);
+        assert_eq!(find_cycle_brent(7, |&x| x), expected);
+    }
+
+    #[test]
+    fn test_find_cycle_large() {
+        let step = |&x: &u64| (x * x + 1) % 1_000_003;
+        assert_eq!(find_cycle(2, step), find_cycle_brent(2, step));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let cycle = find_cycle(0, step);
+        assert_eq!(cycle.normalize(1), 1);
+        assert_eq!(cycle.normalize(5), 2);
+        assert_eq!(cycle.normalize(1_000_000_000), 4);
+    }
+
+    #[test]
+    fn test_state_at() {
+        for n in 0..20 {
+            let expected = (0..n).fold(0, |x, _| step(&x));
+            assert_eq!(state_at(0, step, n), expected);
+            assert_eq!(find_cycle_brent(0, step).state_at(0, step, n), expected);
+        }
+        assert_eq!(state_at(0, step, 1_000_000_000), 4);
+    }
+}
@@ -1,5 +1,8 @@
-use super::Vector2D;
+use std::str::FromStr;
 
+use super::{ParseError, Vector2D};
+
+/// One of the four orthogonal directions on a grid, where Y points down.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Direction {
     N,
@@ -8,6 +11,7 @@ pub enum Direction {
     E,
 }
 
+#[allow(dead_code)]
 impl Direction {
     pub fn step(self) -> Vector2D {
         match self {
@@ -30,4 +34,336 @@ impl Direction {
     pub fn all() -> [Direction; 4] {
         [Direction::N, Direction::S, Direction::W, Direction::E]
     }
+
+    /// Turns 90 degrees counterclockwise.
+    pub fn turn_left(self) -> Direction {
+        match self {
+            Direction::N => Direction::W,
+            Direction::W => Direction::S,
+            Direction::S => Direction::E,
+            Direction::E => Direction::N,
+        }
+    }
+
+    /// Turns 90 degrees clockwise.
+    pub fn turn_right(self) -> Direction {
+        match self {
+            Direction::N => Direction::E,
+            Direction::E => Direction::S,
+            Direction::S => Direction::W,
+            Direction::W => Direction::N,
+        }
+    }
+
+    /// Turns 180 degrees.
+    pub fn turn_around(self) -> Direction {
+        self.opposite()
+    }
+
+    /// Reflects off a `/` mirror, e.g. moving east turns north.
+    pub fn reflect_slash(self) -> Direction {
+        match self {
+            Direction::N => Direction::E,
+            Direction::E => Direction::N,
+            Direction::W => Direction::S,
+            Direction::S => Direction::W,
+        }
+    }
+
+    /// Reflects off a `\` mirror, e.g. moving east turns south.
+    pub fn reflect_backslash(self) -> Direction {
+        match self {
+            Direction::N => Direction::W,
+            Direction::W => Direction::N,
+            Direction::S => Direction::E,
+            Direction::E => Direction::S,
+        }
+    }
+
+    /// Parses a compass direction: `N`, `S`, `W` or `E`.
+    pub fn from_compass(c: char) -> Option<Direction> {
+        match c {
+            'N' => Some(Direction::N),
+            'S' => Some(Direction::S),
+            'W' => Some(Direction::W),
+            'E' => Some(Direction::E),
+            _ => None,
+        }
+    }
+
+    /// Parses an up/down/left/right direction: `U`, `D`, `L` or `R`.
+    pub fn from_udlr(c: char) -> Option<Direction> {
+        match c {
+            'U' => Some(Direction::N),
+            'D' => Some(Direction::S),
+            'L' => Some(Direction::W),
+            'R' => Some(Direction::E),
+            _ => None,
+        }
+    }
+
+    /// Parses an arrow: `^`, `v`, `<` or `>`.
+    pub fn from_arrow(c: char) -> Option<Direction> {
+        match c {
+            '^' => Some(Direction::N),
+            'v' => Some(Direction::S),
+            '<' => Some(Direction::W),
+            '>' => Some(Direction::E),
+            _ => None,
+        }
+    }
+
+    /// The arrow pointing in this direction, as parsed by [`Direction::from_arrow`].
+    pub fn arrow(self) -> char {
+        match self {
+            Direction::N => '^',
+            Direction::S => 'v',
+            Direction::W => '<',
+            Direction::E => '>',
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = ParseError;
+
+    /// Parses a single character in any of the supported notations.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Direction::from_compass(c)
+                .or_else(|| Direction::from_udlr(c))
+                .or_else(|| Direction::from_arrow(c)),
+            _ => None,
+        }
+        .ok_or_else(|| ParseError::expected(s, "direction"))
+    }
+}
+
+/// One of the eight orthogonal or diagonal directions on a grid, where Y points down.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Direction8 {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+#[allow(dead_code)]
+impl Direction8 {
+    /// All directions, in clockwise order.
+    pub fn all() -> [Direction8; 8] {
+        [
+            Direction8::N,
+            Direction8::NE,
+            Direction8::E,
+            Direction8::SE,
+            Direction8::S,
+            Direction8::SW,
+            Direction8::W,
+            Direction8::NW,
+        ]
+    }
+
+    pub fn step(self) -> Vector2D {
+        match self {
+            Direction8::N => Vector2D::new(0, -1),
+            Direction8::NE => Vector2D::new(1, -1),
+            Direction8::E => Vector2D::new(1, 0),
+            Direction8::SE => Vector2D::new(1, 1),
+            Direction8::S => Vector2D::new(0, 1),
+            Direction8::SW => Vector2D::new(-1, 1),
+            Direction8::W => Vector2D::new(-1, 0),
+            Direction8::NW => Vector2D::new(-1, -1),
+        }
+    }
+
+    /// Rotates clockwise by the given number of 45 degree turns.
+    fn rotate(self, turns: usize) -> Direction8 {
+        Self::all()[(self as usize + turns) % 8]
+    }
+
+    pub fn opposite(self) -> Direction8 {
+        self.rotate(4)
+    }
+
+    /// Turns 45 degrees counterclockwise.
+    pub fn turn_left(self) -> Direction8 {
+        self.rotate(7)
+    }
+
+    /// Turns 45 degrees clockwise.
+    pub fn turn_right(self) -> Direction8 {
+        self.rotate(1)
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        self as usize % 2 == 1
+    }
+}
+
+impl From<Direction> for Direction8 {
+    fn from(dir: Direction) -> Self {
+        match dir {
+            Direction::N => Direction8::N,
+            Direction::S => Direction8::S,
+            Direction::W => Direction8::W,
+            Direction::E => Direction8::E,
+        }
+    }
+}
+
+/// One of the six directions on a hex grid with flat-topped hexagons.
+///
+/// Hexes are addressed with axial coordinates: moving north decreases Y,
+/// moving southeast increases X, so moving northeast changes both.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum HexDirection {
+    N,
+    NE,
+    SE,
+    S,
+    SW,
+    NW,
+}
+
+#[allow(dead_code)]
+impl HexDirection {
+    /// All directions, in clockwise order.
+    pub fn all() -> [HexDirection; 6] {
+        [
+            HexDirection::N,
+            HexDirection::NE,
+            HexDirection::SE,
+            HexDirection::S,
+            HexDirection::SW,
+            HexDirection::NW,
+        ]
+    }
+
+    pub fn step(self) -> Vector2D {
+        match self {
+            HexDirection::N => Vector2D::new(0, -1),
+            HexDirection::NE => Vector2D::new(1, -1),
+            HexDirection::SE => Vector2D::new(1, 0),
+            HexDirection::S => Vector2D::new(0, 1),
+            HexDirection::SW => Vector2D::new(-1, 1),
+            HexDirection::NW => Vector2D::new(-1, 0),
+        }
+    }
+
+    /// Rotates clockwise by the given number of 60 degree turns.
+    fn rotate(self, turns: usize) -> HexDirection {
+        Self::all()[(self as usize + turns) % 6]
+    }
+
+    pub fn opposite(self) -> HexDirection {
+        self.rotate(3)
+    }
+
+    /// Turns 60 degrees counterclockwise.
+    pub fn turn_left(self) -> HexDirection {
+        self.rotate(5)
+    }
+
+    /// Turns 60 degrees clockwise.
+    pub fn turn_right(self) -> HexDirection {
+        self.rotate(1)
+    }
+
+    /// The number of steps needed to move by `offset` on a hex grid.
+    pub fn distance(offset: Vector2D) -> i32 {
+        (offset.x().abs() + offset.y().abs() + (offset.x() + offset.y()).abs()) / 2
+    }
+}
+
+impl FromStr for HexDirection {
+    type Err = ParseError;
+
+    /// Parses a lowercase direction, like `n` or `se`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "n" => HexDirection::N,
+            "ne" => HexDirection::NE,
+            "se" => HexDirection::SE,
+            "s" => HexDirection::S,
+            "sw" => HexDirection::SW,
+            "nw" => HexDirection::NW,
+            _ => return Err(ParseError::expected(s, "hex direction")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns() {
+        for dir in Direction::all() {
+            assert_eq!(dir.turn_left().turn_right(), dir);
+            assert_eq!(dir.turn_right().turn_right(), dir.turn_around());
+            assert_eq!(
+                dir.turn_left().step(),
+                Direction8::from(dir).rotate(6).step()
+            );
+        }
+        assert_eq!(Direction::N.turn_left(), Direction::W);
+        assert_eq!(Direction::N.turn_right(), Direction::E);
+    }
+
+    #[test]
+    fn test_reflections() {
+        assert_eq!(Direction::E.reflect_slash(), Direction::N);
+        assert_eq!(Direction::E.reflect_backslash(), Direction::S);
+        for dir in Direction::all() {
+            assert_eq!(dir.reflect_slash().reflect_slash(), dir);
+            assert_eq!(dir.reflect_backslash().reflect_backslash(), dir);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("U".parse(), Ok(Direction::N));
+        assert_eq!("W".parse(), Ok(Direction::W));
+        assert_eq!(">".parse(), Ok(Direction::E));
+        assert_eq!(Direction::from_arrow('v'), Some(Direction::S));
+        assert_eq!(Direction::from_udlr('v'), None);
+        for dir in Direction::all() {
+            assert_eq!(Direction::from_arrow(dir.arrow()), Some(dir));
+        }
+        assert!("UD".parse::<Direction>().is_err());
+        assert!("".parse::<Direction>().is_err());
+    }
+
+    #[test]
+    fn test_direction8() {
+        assert_eq!(Direction8::N.turn_right(), Direction8::NE);
+        assert_eq!(Direction8::N.turn_left(), Direction8::NW);
+        assert_eq!(Direction8::SW.opposite(), Direction8::NE);
+        assert!(Direction8::SE.is_diagonal());
+        for dir in Direction8::all() {
+            assert_eq!(dir.step() + dir.opposite().step(), Vector2D::zero());
+        }
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(HexDirection::N.turn_right(), HexDirection::NE);
+        assert_eq!(HexDirection::N.turn_left(), HexDirection::NW);
+        for dir in HexDirection::all() {
+            assert_eq!(dir.step() + dir.opposite().step(), Vector2D::zero());
+            assert_eq!(HexDirection::distance(dir.step()), 1);
+        }
+        let path = ["ne", "ne", "s", "s"].map(|s| s.parse::<HexDirection>().unwrap());
+        let offset = path
+            .iter()
+            .map(|dir| dir.step())
+            .fold(Vector2D::zero(), |a, b| a + b);
+        assert_eq!(HexDirection::distance(offset), 2);
+    }
 }
@@ -0,0 +1,321 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+
+use super::{ParseError, Vector2D};
+
+/// A dense, rectangular grid of cells, stored row by row.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+#[allow(dead_code)]
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
+    pub fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Self {
+        assert_eq!(cells.len(), width * height);
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Parses a grid with one cell per character.
+    ///
+    /// Fails if a character is not accepted by `f`, or if the lines have different lengths.
+    pub fn parse(
+        input: &str,
+        expected: &str,
+        mut f: impl FnMut(char) -> Option<T>,
+    ) -> Result<Self, ParseError> {
+        let width = input.lines().next().unwrap_or_default().chars().count();
+        let mut height = 0;
+        let mut cells = Vec::with_capacity(input.len());
+        for line in input.lines() {
+            let mut x = 0;
+            for (i, c) in line.char_indices() {
+                if x == width {
+                    return Err(ParseError::expected(&line[i..], "end of line"));
+                }
+                cells.push(f(c).ok_or_else(|| ParseError::expected(&line[i..], expected))?);
+                x += 1;
+            }
+            if x < width {
+                return Err(ParseError::expected_after(line, expected));
+            }
+            height += 1;
+        }
+        Ok(Self {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn contains(&self, pos: Vector2D) -> bool {
+        (0..self.width as i32).contains(&pos.x()) && (0..self.height as i32).contains(&pos.y())
+    }
+
+    #[inline]
+    fn index_of(&self, pos: Vector2D) -> Option<usize> {
+        if self.contains(pos) {
+            Some(pos.y() as usize * self.width + pos.x() as usize)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, pos: Vector2D) -> Option<&T> {
+        self.index_of(pos).map(|i| &self.cells[i])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, pos: Vector2D) -> Option<&mut T> {
+        self.index_of(pos).map(|i| &mut self.cells[i])
+    }
+
+    /// Iterates over all positions, row by row.
+    pub fn positions(&self) -> impl Iterator<Item = Vector2D> {
+        let width = self.width;
+        (0..self.height)
+            .flat_map(move |y| (0..width).map(move |x| Vector2D::new(x as i32, y as i32)))
+    }
+
+    /// Iterates over all cells together with their positions, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector2D, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    /// Finds the position of the first cell matching `predicate`.
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<Vector2D> {
+        self.iter()
+            .find_map(|(pos, cell)| if predicate(cell) { Some(pos) } else { None })
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // Not `chunks_exact`, which can't produce empty rows when the width is zero
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width);
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(|x| self.column(x))
+    }
+
+    /// The orthogonal neighbours of `pos` that lie inside the grid.
+    pub fn neighbours(&self, pos: Vector2D) -> impl Iterator<Item = Vector2D> + '_ {
+        pos.neighbours().filter(|&pos| self.contains(pos))
+    }
+
+    /// The orthogonal and diagonal neighbours of `pos` that lie inside the grid.
+    pub fn neighbours_diagonal(&self, pos: Vector2D) -> impl Iterator<Item = Vector2D> + '_ {
+        pos.neighbours_diagonal().filter(|&pos| self.contains(pos))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// Builds a new grid of the given size, taking each cell from `f(x, y)`.
+    fn from_fn(width: usize, height: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(f(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Mirrors the grid along its main diagonal, swapping rows and columns.
+    pub fn transpose(&self) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(self.height, self.width, |x, y| {
+            self.cells[x * self.width + y].clone()
+        })
+    }
+
+    /// Rotates the grid by 90 degrees, so the left column becomes the top row.
+    pub fn rotate_clockwise(&self) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(self.height, self.width, |x, y| {
+            self.cells[(self.height - 1 - x) * self.width + y].clone()
+        })
+    }
+
+    /// Rotates the grid by 90 degrees, so the right column becomes the top row.
+    pub fn rotate_counterclockwise(&self) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(self.height, self.width, |x, y| {
+            self.cells[x * self.width + (self.width - 1 - y)].clone()
+        })
+    }
+}
+
+impl<T> Index<Vector2D> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: Vector2D) -> &Self::Output {
+        self.get(pos)
+            .unwrap_or_else(|| panic!("position {pos} out of bounds"))
+    }
+}
+
+impl<T> IndexMut<Vector2D> for Grid<T> {
+    fn index_mut(&mut self, pos: Vector2D) -> &mut Self::Output {
+        self.get_mut(pos)
+            .unwrap_or_else(|| panic!("position {pos} out of bounds"))
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (y, row) in self.rows().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "ab\ncd\nef";
+
+    fn parse(input: &str) -> Grid<char> {
+        Grid::parse(input, "letter", Some).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let grid = parse(INPUT);
+        assert_eq!((grid.width(), grid.height()), (2, 3));
+        assert_eq!(grid.get(Vector2D::new(1, 2)), Some(&'f'));
+        assert_eq!(grid.get(Vector2D::new(2, 0)), None);
+        assert_eq!(grid.get(Vector2D::new(0, -1)), None);
+        assert_eq!(grid[Vector2D::new(0, 1)], 'c');
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = "ab\nc";
+        let error = Grid::parse(input, "letter", Some).unwrap_err();
+        assert_eq!(error.locate(1, input).column, Some(2));
+        let input = "ab\nc?";
+        let error = Grid::parse(input, "letter", |c| c.is_alphabetic().then_some(c));
+        assert_eq!(error.unwrap_err().locate(1, input).column, Some(2));
+    }
+
+    #[test]
+    fn test_rows_and_columns() {
+        let grid = parse(INPUT);
+        assert_eq!(grid.row(1), &['c', 'd']);
+        assert_eq!(grid.rows().count(), 3);
+        assert_eq!(grid.column(1).collect::<String>(), "bdf");
+        assert_eq!(
+            grid.columns()
+                .map(|column| column.collect::<String>())
+                .collect::<Vec<_>>(),
+            vec!["ace", "bdf"]
+        );
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let grid = Grid::new(0, 3, 'a');
+        assert_eq!(grid.rows().count(), 3);
+        assert!(grid.rows().all(|row| row.is_empty()));
+        assert_eq!(grid.columns().count(), 0);
+        assert_eq!(grid.to_string(), "\n\n");
+        let grid = parse("\n\n");
+        assert_eq!((grid.width(), grid.height()), (0, 2));
+        assert_eq!(grid.rows().count(), 2);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let grid = parse(INPUT);
+        assert_eq!(
+            grid.neighbours(Vector2D::new(0, 0)).collect::<Vec<_>>(),
+            vec![Vector2D::new(1, 0), Vector2D::new(0, 1)]
+        );
+        assert_eq!(grid.neighbours_diagonal(Vector2D::new(1, 1)).count(), 5);
+    }
+
+    #[test]
+    fn test_find() {
+        let grid = parse(INPUT);
+        assert_eq!(grid.find(|&c| c == 'd'), Some(Vector2D::new(1, 1)));
+        assert_eq!(grid.find(|&c| c == 'z'), None);
+    }
+
+    #[test]
+    fn test_transform() {
+        let grid = parse(INPUT);
+        assert_eq!(grid.transpose().to_string(), "ace\nbdf");
+        assert_eq!(grid.rotate_clockwise().to_string(), "eca\nfdb");
+        assert_eq!(grid.rotate_counterclockwise().to_string(), "bdf\nace");
+        assert_eq!(
+            grid.rotate_clockwise().rotate_counterclockwise(),
+            grid.clone()
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(parse(INPUT).to_string(), INPUT);
+    }
+}
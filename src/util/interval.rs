@@ -0,0 +1,318 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+
+use super::Num;
+
+/// A set of values, stored as a sorted list of disjoint half-open ranges.
+///
+/// Overlapping and adjacent ranges are always merged together,
+/// so two sets with the same values also have the same ranges.
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct IntervalSet<T: Num> {
+    ranges: Vec<Range<T>>,
+}
+
+#[allow(dead_code)]
+impl<T: Num> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self { ranges: Vec::new() }
+    }
+
+    pub fn from_ranges(ranges: impl IntoIterator<Item = Range<T>>) -> Self {
+        let mut set = Self {
+            ranges: ranges.into_iter().collect(),
+        };
+        set.normalize();
+        set
+    }
+
+    /// Sorts the ranges, drops empty ones and merges overlapping or adjacent ones.
+    fn normalize(&mut self) {
+        self.ranges.retain(|range| range.start < range.end);
+        self.ranges
+            .sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        let mut merged = Vec::<Range<T>>::with_capacity(self.ranges.len());
+        for range in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => {
+                    if last.end < range.end {
+                        last.end = range.end;
+                    }
+                }
+                _ => merged.push(range),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    pub fn ranges(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The smallest value in the set.
+    pub fn min(&self) -> Option<T> {
+        self.ranges.first().map(|range| range.start)
+    }
+
+    /// The (exclusive) upper bound of the set.
+    pub fn end(&self) -> Option<T> {
+        self.ranges.last().map(|range| range.end)
+    }
+
+    /// The total length of all ranges.
+    pub fn len(&self) -> T {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        // Find the last range starting at or before the value
+        let index = self.ranges.partition_point(|range| range.start <= value);
+        index > 0 && value < self.ranges[index - 1].end
+    }
+
+    pub fn contains_range(&self, range: &Range<T>) -> bool {
+        if range.start >= range.end {
+            return true;
+        }
+        let index = self.ranges.partition_point(|r| r.start <= range.start);
+        index > 0 && range.end <= self.ranges[index - 1].end
+    }
+
+    pub fn insert(&mut self, range: Range<T>) {
+        self.ranges.push(range);
+        self.normalize();
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_ranges(self.ranges.iter().chain(other.ranges.iter()).cloned())
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let left = &self.ranges[i];
+            let right = &other.ranges[j];
+            let start = if left.start < right.start {
+                right.start
+            } else {
+                left.start
+            };
+            let end = if left.end < right.end {
+                left.end
+            } else {
+                right.end
+            };
+            if start < end {
+                ranges.push(start..end);
+            }
+            // Advance whichever range ends first
+            if left.end < right.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { ranges }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let mut j = 0;
+        for range in &self.ranges {
+            let mut start = range.start;
+            // Skip removed ranges that end before this range
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < range.end {
+                let removed = &other.ranges[k];
+                if start < removed.start {
+                    ranges.push(start..removed.start);
+                }
+                if start < removed.end {
+                    start = removed.end;
+                }
+                k += 1;
+            }
+            if start < range.end {
+                ranges.push(start..range.end);
+            }
+        }
+        Self { ranges }
+    }
+
+    /// All values within `bounds` that are not in this set.
+    pub fn complement(&self, bounds: Range<T>) -> Self {
+        Self::from(bounds).difference(self)
+    }
+
+    /// Splits into all values below `value`, and all values at or above `value`.
+    pub fn split_at(&self, value: T) -> (Self, Self) {
+        let mut below = Vec::new();
+        let mut above = Vec::new();
+        for range in &self.ranges {
+            if range.end <= value {
+                below.push(range.clone());
+            } else if value <= range.start {
+                above.push(range.clone());
+            } else {
+                below.push(range.start..value);
+                above.push(value..range.end);
+            }
+        }
+        (Self { ranges: below }, Self { ranges: above })
+    }
+
+    /// Moves every value by `offset`.
+    pub fn shift(&self, offset: T) -> Self {
+        Self {
+            ranges: self
+                .ranges
+                .iter()
+                .map(|range| (range.start + offset)..(range.end + offset))
+                .collect(),
+        }
+    }
+
+    /// Transforms every range with `f`, merging the results.
+    pub fn map_ranges(&self, f: impl FnMut(Range<T>) -> Range<T>) -> Self {
+        Self::from_ranges(self.ranges.iter().cloned().map(f))
+    }
+}
+
+impl<T: Num> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Num> From<Range<T>> for IntervalSet<T> {
+    fn from(range: Range<T>) -> Self {
+        Self::from_ranges([range])
+    }
+}
+
+impl<T: Num> FromIterator<Range<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        Self::from_ranges(iter)
+    }
+}
+
+impl<T: Num> Debug for IntervalSet<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.ranges.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[Range<i64>]) -> IntervalSet<i64> {
+        IntervalSet::from_ranges(ranges.iter().cloned())
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(set(&[5..7, 1..3, 2..4]).ranges(), &[1..4, 5..7]);
+        assert_eq!(set(&[1..3, 3..5]).ranges(), &[1..5]);
+        assert_eq!(set(&[1..10, 2..3]).ranges(), &[1..10]);
+        assert_eq!(set(&[3..3, Range { start: 5, end: 4 }]).ranges(), &[]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let set = set(&[1..3, 5..7]);
+        assert!(!set.contains(0));
+        assert!(set.contains(1));
+        assert!(set.contains(2));
+        assert!(!set.contains(3));
+        assert!(set.contains(6));
+        assert!(!set.contains(7));
+        assert!(set.contains_range(&(5..7)));
+        assert!(!set.contains_range(&(2..6)));
+        assert!(set.contains_range(&(4..4)));
+    }
+
+    #[test]
+    fn test_len() {
+        assert_eq!(set(&[1..3, 5..10]).len(), 7);
+        assert_eq!(set(&[]).len(), 0);
+        assert_eq!(set(&[1..3, 5..10]).min(), Some(1));
+        assert_eq!(set(&[1..3, 5..10]).end(), Some(10));
+    }
+
+    #[test]
+    fn test_union() {
+        assert_eq!(
+            set(&[1..3, 8..10]).union(&set(&[2..5, 10..12])),
+            set(&[1..5, 8..12])
+        );
+    }
+
+    #[test]
+    fn test_intersection() {
+        assert_eq!(
+            set(&[1..5, 8..12]).intersection(&set(&[0..2, 4..9, 11..20])),
+            set(&[1..2, 4..5, 8..9, 11..12])
+        );
+        assert_eq!(set(&[1..5]).intersection(&set(&[5..9])), set(&[]));
+    }
+
+    #[test]
+    fn test_difference() {
+        assert_eq!(
+            set(&[1..10, 20..30]).difference(&set(&[0..2, 4..6, 9..21, 25..26])),
+            set(&[2..4, 6..9, 21..25, 26..30])
+        );
+        assert_eq!(set(&[1..10]).difference(&set(&[])), set(&[1..10]));
+        assert_eq!(set(&[1..10]).difference(&set(&[0..10])), set(&[]));
+    }
+
+    #[test]
+    fn test_complement() {
+        assert_eq!(
+            set(&[1..3, 5..7]).complement(0..10),
+            set(&[0..1, 3..5, 7..10])
+        );
+        assert_eq!(set(&[]).complement(0..10), set(&[0..10]));
+    }
+
+    #[test]
+    fn test_split_at() {
+        let (below, above) = set(&[1..3, 5..7]).split_at(6);
+        assert_eq!(below, set(&[1..3, 5..6]));
+        assert_eq!(above, set(&[6..7]));
+        let (below, above) = set(&[1..3]).split_at(0);
+        assert_eq!(below, set(&[]));
+        assert_eq!(above, set(&[1..3]));
+    }
+
+    #[test]
+    fn test_shift_and_map() {
+        assert_eq!(set(&[1..3, 5..7]).shift(-1), set(&[0..2, 4..6]));
+        assert_eq!(
+            set(&[1..3, 5..7]).map_ranges(|range| (range.start * 2)..(range.end * 2)),
+            set(&[2..6, 10..14])
+        );
+        assert_eq!(
+            set(&[1..3, 5..7]).map_ranges(|range| 0..range.end),
+            set(&[0..7])
+        );
+    }
+
+    #[test]
+    fn test_debug() {
+        assert_eq!(format!("{:?}", set(&[1..3, 5..7])), "{1..3, 5..7}");
+    }
+}
@@ -1,3 +1,5 @@
+use num_traits::{CheckedAdd, CheckedMul};
+
 use super::Num;
 
 #[allow(dead_code)]
@@ -12,5 +14,173 @@ pub fn gcd<T: Num>(mut a: T, mut b: T) -> T {
 
 #[allow(dead_code)]
 pub fn lcm<T: Num>(a: T, b: T) -> T {
-    (a * b) / gcd(a, b)
+    if a == T::zero() || b == T::zero() {
+        return T::zero();
+    }
+    // Divide first, so the intermediate result is never larger than the answer
+    (a / gcd(a, b) * b).abs()
+}
+
+/// Like [`lcm`], but returns `None` if the result overflows.
+#[allow(dead_code)]
+pub fn checked_lcm<T: Num + CheckedMul>(a: T, b: T) -> Option<T> {
+    if a == T::zero() || b == T::zero() {
+        return Some(T::zero());
+    }
+    (a / gcd(a, b)).checked_mul(&b).map(Num::abs)
+}
+
+/// Finds `(g, x, y)` such that `a * x + b * y = g`, where `g` is the GCD of `a` and `b`.
+///
+/// See: https://en.wikipedia.org/wiki/Extended_Euclidean_algorithm
+#[allow(dead_code)]
+pub fn extended_gcd<T: Num>(a: T, b: T) -> (T, T, T) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (T::one(), T::zero());
+    let (mut old_y, mut y) = (T::zero(), T::one());
+    while r != T::zero() {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_x, x) = (x, old_x - quotient * x);
+        (old_y, y) = (y, old_y - quotient * y);
+    }
+    if old_r < T::zero() {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// The remainder of `a` divided by `modulus`, between zero (inclusive) and `modulus` (exclusive).
+#[allow(dead_code)]
+pub fn modulo<T: Num>(a: T, modulus: T) -> T {
+    let modulus = modulus.abs();
+    let remainder = a % modulus;
+    if remainder < T::zero() {
+        remainder + modulus
+    } else {
+        remainder
+    }
+}
+
+/// Finds `x` such that `a * x ≡ 1 (mod modulus)`, if it exists.
+#[allow(dead_code)]
+pub fn mod_inverse<T: Num>(a: T, modulus: T) -> Option<T> {
+    let (g, x, _) = extended_gcd(modulo(a, modulus), modulus.abs());
+    (g == T::one()).then(|| modulo(x, modulus))
+}
+
+/// Solves a system of congruences `x ≡ residue (mod modulus)`,
+/// given as `(residue, modulus)` pairs with positive moduli.
+///
+/// The moduli need not be pairwise coprime. Returns the combined `(residue, modulus)`,
+/// where the modulus is the LCM of all moduli and the residue is the smallest solution,
+/// or `None` if the congruences contradict each other or the modulus overflows.
+///
+/// See: https://en.wikipedia.org/wiki/Chinese_remainder_theorem#Generalization_to_non-coprime_moduli
+#[allow(dead_code)]
+pub fn crt<T: Num + CheckedAdd + CheckedMul>(
+    congruences: impl IntoIterator<Item = (T, T)>,
+) -> Option<(T, T)> {
+    let mut result = (T::zero(), T::one());
+    for (residue, modulus) in congruences {
+        let (result_residue, result_modulus) = result;
+        let residue = modulo(residue, modulus);
+        let (g, x, _) = extended_gcd(result_modulus, modulus);
+        let diff = residue - result_residue;
+        if diff % g != T::zero() {
+            return None;
+        }
+        // Find k such that result_residue + result_modulus * k ≡ residue (mod modulus)
+        let step = modulus / g;
+        let k = modulo(modulo(diff / g, step).checked_mul(&modulo(x, step))?, step);
+        let combined_modulus = checked_lcm(result_modulus, modulus)?;
+        let combined_residue = result_residue.checked_add(&result_modulus.checked_mul(&k)?)?;
+        result = (modulo(combined_residue, combined_modulus), combined_modulus);
+    }
+    Some(result)
+}
+
+/// The largest integer whose square is at most `n`, for integer types.
+///
+/// Panics if `n` is negative.
+#[allow(dead_code)]
+pub fn isqrt<T: Num>(n: T) -> T {
+    assert!(n >= T::zero(), "square root of negative number");
+    let two = T::one() + T::one();
+    if n < two {
+        return n;
+    }
+    // Newton's method, starting from an overestimate so it decreases monotonically
+    let mut x = n / two + T::one();
+    loop {
+        let y = (x + n / x) / two;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gcd_lcm() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(-12, 18), 6);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(0, 6), 0);
+        assert_eq!(lcm(-4, 6), 12);
+        assert_eq!(lcm(i64::MAX, 1), i64::MAX);
+        assert_eq!(checked_lcm(1i64 << 40, 3 << 30), Some(3 << 40));
+        assert_eq!(checked_lcm(1i64 << 40, (1 << 30) - 1), None);
+    }
+
+    #[test]
+    fn test_extended_gcd() {
+        for (a, b) in [(240, 46), (46, 240), (-7, 3), (0, 5), (5, 0), (12, -18)] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(g, gcd(a, b), "gcd of {a} and {b}");
+            assert_eq!(a * x + b * y, g, "coefficients for {a} and {b}");
+        }
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(-3, 11), Some(7));
+        assert_eq!(mod_inverse(6, 9), None);
+        assert_eq!(modulo(-7, 3), 2);
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt([(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        // Non-coprime moduli
+        assert_eq!(crt([(3, 4), (5, 6)]), Some((11, 12)));
+        assert_eq!(crt([(3, 4), (4, 6)]), None);
+        assert_eq!(crt::<i64>([]), Some((0, 1)));
+        // Large moduli, like cycle lengths
+        let moduli = [20_093i64, 12_169, 22_357, 14_999, 17_263, 16_697];
+        let (residue, modulus) = crt(moduli.map(|m| (m - 5, m))).unwrap();
+        assert_eq!(modulus, moduli.into_iter().fold(1, lcm));
+        assert_eq!(residue, modulus - 5);
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for n in 0..1000i64 {
+            let root = isqrt(n);
+            assert!(
+                root * root <= n && (root + 1) * (root + 1) > n,
+                "isqrt({n})"
+            );
+        }
+        assert_eq!(isqrt(i64::MAX), 3_037_000_499);
+        assert_eq!(isqrt(1i128 << 100), 1 << 50);
+        assert_eq!(isqrt(i128::MAX), 13_043_817_825_332_782_212);
+    }
 }
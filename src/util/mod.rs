@@ -1,11 +1,25 @@
+pub use cuboid::*;
+pub use cycle::*;
 pub use direction::*;
+pub use grid::*;
+pub use interval::*;
 pub use math::*;
 pub use num::*;
+pub use parse::*;
+pub use polygon::*;
+pub use ratio::*;
 pub use slice::*;
 pub use vector::*;
 
+mod cuboid;
+mod cycle;
 mod direction;
+mod grid;
+mod interval;
 mod math;
 mod num;
+mod parse;
+mod polygon;
+mod ratio;
 mod slice;
 mod vector;
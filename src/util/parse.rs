@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use super::{Num, Vector};
+
+/// An error in the puzzle input, pointing at the place where parsing went wrong.
+///
+/// Parsers create errors with [`ParseError::expected`] from the remaining text they failed on.
+/// The generator then calls [`ParseError::locate`] with the full puzzle input,
+/// which turns that text into a line and column number.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub day: Option<u32>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub expected: String,
+    /// Address of the offending text, until it is resolved into a line and column.
+    addr: usize,
+}
+
+impl ParseError {
+    /// Creates an error for when `expected` was expected at the start of `at`.
+    ///
+    /// `at` must be a slice of the original input, so the error can be located later on.
+    pub fn expected(at: &str, expected: impl Display) -> Self {
+        Self {
+            day: None,
+            line: None,
+            column: None,
+            expected: expected.to_string(),
+            addr: at.as_ptr() as usize,
+        }
+    }
+
+    /// Creates an error for when `expected` was expected after the end of `s`.
+    pub fn expected_after(s: &str, expected: impl Display) -> Self {
+        Self::expected(&s[s.len()..], expected)
+    }
+
+    /// Resolves the line and column of this error within the full puzzle `input`.
+    pub fn locate(mut self, day: u32, input: &str) -> Self {
+        self.day = Some(day);
+        let start = input.as_ptr() as usize;
+        if self.line.is_none() && (start..=start + input.len()).contains(&self.addr) {
+            let before = &input[..self.addr - start];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            self.line = Some(before.matches('\n').count() + 1);
+            self.column = Some(before[line_start..].chars().count() + 1);
+        }
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "parse error")?;
+        if let Some(day) = self.day {
+            write!(f, " in day {day}")?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " at line {line}, column {column}")?;
+        }
+        write!(f, ": expected {}", self.expected)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Like `str::split_once()`, but fails if the delimiter is missing.
+pub fn split_once<'a>(s: &'a str, delimiter: &str) -> Result<(&'a str, &'a str), ParseError> {
+    s.split_once(delimiter)
+        .ok_or_else(|| ParseError::expected_after(s, format!("{delimiter:?}")))
+}
+
+/// Like `str::strip_prefix()`, but fails if the prefix is missing.
+pub fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Result<&'a str, ParseError> {
+    s.strip_prefix(prefix)
+        .ok_or_else(|| ParseError::expected(s, format!("{prefix:?}")))
+}
+
+/// Like `str::strip_suffix()`, but fails if the suffix is missing.
+pub fn strip_suffix<'a>(s: &'a str, suffix: &str) -> Result<&'a str, ParseError> {
+    s.strip_suffix(suffix)
+        .ok_or_else(|| ParseError::expected_after(s, format!("{suffix:?}")))
+}
+
+/// Parses a number, or any other type whose parse errors carry no location.
+pub fn parse_number<T: FromStr>(s: &str) -> Result<T, ParseError> {
+    s.parse().map_err(|_| ParseError::expected(s, "number"))
+}
+
+/// Takes the next item from `iter`, or fails at the end of `s`.
+pub fn next_item<T>(
+    iter: &mut impl Iterator<Item = T>,
+    s: &str,
+    expected: &str,
+) -> Result<T, ParseError> {
+    iter.next()
+        .ok_or_else(|| ParseError::expected_after(s, expected))
+}
+
+/// Parses a vector from exactly `N` coordinates, separated by `separator`.
+pub fn parse_vector<const N: usize, T: Num + FromStr>(
+    s: &str,
+    separator: &str,
+) -> Result<Vector<N, T>, ParseError> {
+    let mut coords = s.split(separator);
+    let mut vector = Vector::zero();
+    for coord in vector.coords.iter_mut() {
+        *coord = parse_number(next_item(&mut coords, s, "coordinate")?.trim())?;
+    }
+    if coords.next().is_some() {
+        return Err(ParseError::expected(s, format!("{N} coordinates")));
+    }
+    Ok(vector)
+}
+
+/// Parses every line of `input`.
+pub fn parse_lines<T: FromStr<Err = ParseError>>(input: &str) -> Result<Vec<T>, ParseError> {
+    input.lines().map(str::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let input = "first line\nsecond line";
+        let error = ParseError::expected(&input[18..], "number").locate(42, input);
+        assert_eq!(error.day, Some(42));
+        assert_eq!(error.line, Some(2));
+        assert_eq!(error.column, Some(8));
+        assert_eq!(
+            error.to_string(),
+            "parse error in day 42 at line 2, column 8: expected number"
+        );
+    }
+
+    #[test]
+    fn test_locate_end() {
+        let input = "a: b\nc";
+        let error = split_once(&input[5..], ": ").unwrap_err().locate(1, input);
+        assert_eq!((error.line, error.column), (Some(2), Some(2)));
+        assert_eq!(error.expected, "\": \"");
+    }
+
+    #[test]
+    fn test_unknown_location() {
+        let input = String::from("other");
+        let error = parse_number::<u32>("abc").unwrap_err().locate(1, &input);
+        assert_eq!((error.line, error.column), (None, None));
+        assert_eq!(error.to_string(), "parse error in day 1: expected number");
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "1\n2\nx\n4";
+        let error = parse_lines::<Wrapper>(input).unwrap_err().locate(1, input);
+        assert_eq!((error.line, error.column), (Some(3), Some(1)));
+    }
+
+    #[test]
+    fn test_parse_vector() {
+        assert_eq!(
+            parse_vector::<3, i32>("1, -2,  3", ","),
+            Ok(Vector::from([1, -2, 3]))
+        );
+        assert!(parse_vector::<3, i32>("1,2", ",").is_err());
+        assert!(parse_vector::<3, i32>("1,2,3,4", ",").is_err());
+    }
+
+    #[derive(Debug)]
+    struct Wrapper;
+
+    impl FromStr for Wrapper {
+        type Err = ParseError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_number::<u32>(s).map(|_| Wrapper)
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use super::{gcd, Direction, Num, Vector2D};
+
+/// The winding direction of a polygon's vertices.
+///
+/// This uses the mathematical convention where the Y axis points up.
+/// On a grid where Y points down, the two are swapped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+    /// All vertices lie on a single line.
+    Degenerate,
+}
+
+/// Where a point lies relative to a polygon.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Location {
+    Inside,
+    Boundary,
+    Outside,
+}
+
+/// A simple polygon, given by its vertices in order.
+/// The last vertex connects back to the first one.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Polygon<T: Num = i32> {
+    vertices: Vec<Vector2D<T>>,
+}
+
+/// The unit step in the given direction.
+fn unit_step<T: Num>(dir: Direction) -> Vector2D<T> {
+    let (zero, one) = (T::zero(), T::one());
+    match dir {
+        Direction::N => Vector2D::new(zero, -one),
+        Direction::S => Vector2D::new(zero, one),
+        Direction::W => Vector2D::new(-one, zero),
+        Direction::E => Vector2D::new(one, zero),
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Num> Polygon<T> {
+    /// Creates a polygon from its vertices.
+    /// If the last vertex repeats the first one, it is dropped.
+    pub fn new(mut vertices: Vec<Vector2D<T>>) -> Self {
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        Self { vertices }
+    }
+
+    /// Creates a polygon by walking `length` steps in each direction, starting from `start`.
+    pub fn from_steps(start: Vector2D<T>, steps: impl IntoIterator<Item = (Direction, T)>) -> Self {
+        let mut pos = start;
+        let mut vertices = vec![pos];
+        for (dir, length) in steps {
+            pos += unit_step(dir) * length;
+            vertices.push(pos);
+        }
+        Self::new(vertices)
+    }
+
+    pub fn vertices(&self) -> &[Vector2D<T>] {
+        &self.vertices
+    }
+
+    /// Iterates over all edges, including the one closing the polygon.
+    pub fn edges(&self) -> impl Iterator<Item = (Vector2D<T>, Vector2D<T>)> + '_ {
+        let next = self.vertices.iter().cycle().skip(1);
+        self.vertices.iter().copied().zip(next.copied())
+    }
+
+    /// Twice the signed area, which is always an integer for lattice polygons.
+    /// Positive when the vertices are ordered counterclockwise.
+    ///
+    /// See: https://en.wikipedia.org/wiki/Shoelace_formula
+    pub fn signed_area_x2(&self) -> T {
+        self.edges()
+            .map(|(pos, next_pos)| pos.x() * next_pos.y() - next_pos.x() * pos.y())
+            .sum()
+    }
+
+    /// Twice the (unsigned) area.
+    pub fn area_x2(&self) -> T {
+        self.signed_area_x2().abs()
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area_x2().as_() / 2.0
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        let area = self.signed_area_x2();
+        if area > T::zero() {
+            Orientation::CounterClockwise
+        } else if area < T::zero() {
+            Orientation::Clockwise
+        } else {
+            Orientation::Degenerate
+        }
+    }
+
+    /// The total (Euclidean) length of all edges.
+    pub fn perimeter(&self) -> f64 {
+        self.edges()
+            .map(|(pos, next_pos)| {
+                let diff = (next_pos - pos).to_f64();
+                diff.dot_product(diff).sqrt()
+            })
+            .sum()
+    }
+
+    /// The number of lattice points on the boundary.
+    pub fn boundary_points(&self) -> T {
+        self.edges()
+            .map(|(pos, next_pos)| {
+                let diff = next_pos - pos;
+                gcd(diff.x(), diff.y())
+            })
+            .sum()
+    }
+
+    /// The number of lattice points strictly inside the polygon.
+    ///
+    /// See: https://en.wikipedia.org/wiki/Pick%27s_theorem
+    pub fn interior_points(&self) -> T {
+        // A = I + B/2 - 1, so I = (2A - B + 2) / 2
+        let two = T::one() + T::one();
+        (self.area_x2() - self.boundary_points() + two) / two
+    }
+
+    /// The number of lattice points inside or on the boundary of the polygon.
+    pub fn lattice_points(&self) -> T {
+        self.interior_points() + self.boundary_points()
+    }
+
+    /// Finds whether `point` lies inside, outside or on the boundary of the polygon.
+    ///
+    /// See: https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm
+    pub fn locate(&self, point: Vector2D<T>) -> Location {
+        let mut inside = false;
+        for (start, end) in self.edges() {
+            let edge = end - start;
+            let to_point = point - start;
+            let cross = edge.x() * to_point.y() - edge.y() * to_point.x();
+            if cross == T::zero()
+                && to_point.dot_product(edge) >= T::zero()
+                && to_point.dot_product(to_point) <= edge.dot_product(edge)
+            {
+                return Location::Boundary;
+            }
+            // Does a ray going right from the point cross this edge?
+            if (start.y() > point.y()) != (end.y() > point.y()) {
+                let crosses = if end.y() > start.y() {
+                    cross > T::zero()
+                } else {
+                    cross < T::zero()
+                };
+                if crosses {
+                    inside = !inside;
+                }
+            }
+        }
+        if inside {
+            Location::Inside
+        } else {
+            Location::Outside
+        }
+    }
+
+    /// Checks whether `point` lies strictly inside the polygon.
+    pub fn contains(&self, point: Vector2D<T>) -> bool {
+        self.locate(point) == Location::Inside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The 4x3 rectangle with corners (0, 0) and (4, 3), counterclockwise.
+    fn rectangle() -> Polygon {
+        Polygon::new(vec![
+            Vector2D::new(0, 0),
+            Vector2D::new(4, 0),
+            Vector2D::new(4, 3),
+            Vector2D::new(0, 3),
+        ])
+    }
+
+    #[test]
+    fn test_area() {
+        let rectangle = rectangle();
+        assert_eq!(rectangle.signed_area_x2(), 24);
+        assert_eq!(rectangle.area(), 12.0);
+        assert_eq!(rectangle.perimeter(), 14.0);
+        assert_eq!(rectangle.orientation(), Orientation::CounterClockwise);
+        let reversed = Polygon::new(rectangle.vertices().iter().rev().copied().collect());
+        assert_eq!(reversed.signed_area_x2(), -24);
+        assert_eq!(reversed.orientation(), Orientation::Clockwise);
+    }
+
+    #[test]
+    fn test_triangle() {
+        let triangle = Polygon::new(vec![
+            Vector2D::new(0, 0),
+            Vector2D::new(4, 0),
+            Vector2D::new(0, 2),
+        ]);
+        assert_eq!(triangle.area(), 4.0);
+        assert_eq!(triangle.boundary_points(), 8);
+        assert_eq!(triangle.interior_points(), 1);
+        assert_eq!(triangle.perimeter(), 6.0 + 20f64.sqrt());
+    }
+
+    #[test]
+    fn test_pick() {
+        let rectangle = rectangle();
+        assert_eq!(rectangle.boundary_points(), 14);
+        assert_eq!(rectangle.interior_points(), 6);
+        assert_eq!(rectangle.lattice_points(), 20);
+    }
+
+    #[test]
+    fn test_from_steps() {
+        let steps = [
+            (Direction::E, 4),
+            (Direction::S, 3),
+            (Direction::W, 4),
+            (Direction::N, 3),
+        ];
+        let polygon = Polygon::from_steps(Vector2D::new(0, 0), steps);
+        assert_eq!(polygon.vertices().len(), 4);
+        assert_eq!(polygon.area_x2(), 24);
+        // Clockwise on screen means counterclockwise with the Y axis pointing up
+        assert_eq!(polygon.orientation(), Orientation::CounterClockwise);
+    }
+
+    #[test]
+    fn test_locate() {
+        // An L shape
+        let polygon = Polygon::new(vec![
+            Vector2D::new(0, 0),
+            Vector2D::new(4, 0),
+            Vector2D::new(4, 2),
+            Vector2D::new(2, 2),
+            Vector2D::new(2, 4),
+            Vector2D::new(0, 4),
+        ]);
+        assert_eq!(polygon.locate(Vector2D::new(1, 1)), Location::Inside);
+        assert_eq!(polygon.locate(Vector2D::new(1, 3)), Location::Inside);
+        assert_eq!(polygon.locate(Vector2D::new(3, 3)), Location::Outside);
+        assert_eq!(polygon.locate(Vector2D::new(2, 3)), Location::Boundary);
+        assert_eq!(polygon.locate(Vector2D::new(0, 0)), Location::Boundary);
+        assert_eq!(polygon.locate(Vector2D::new(-1, 2)), Location::Outside);
+        assert_eq!(polygon.locate(Vector2D::new(5, 0)), Location::Outside);
+        assert!(polygon.contains(Vector2D::new(3, 1)));
+        assert!(!polygon.contains(Vector2D::new(4, 1)));
+    }
+}
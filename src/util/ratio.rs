@@ -0,0 +1,356 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::Sum;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+
+use num_traits::{AsPrimitive, One, Zero};
+
+use super::{gcd, Num, ParseError};
+
+/// An exact rational number, always stored in lowest terms with a positive denominator.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Ratio {
+    numer: i128,
+    denom: i128,
+}
+
+#[allow(dead_code)]
+impl Ratio {
+    /// Creates the fraction `numer / denom`.
+    ///
+    /// Panics if `denom` is zero.
+    pub fn new(numer: i128, denom: i128) -> Self {
+        assert_ne!(denom, 0, "denominator must not be zero");
+        let divisor = gcd(numer, denom) * denom.signum();
+        Self {
+            numer: numer / divisor,
+            denom: denom / divisor,
+        }
+    }
+
+    pub const fn from_integer(value: i128) -> Self {
+        Self {
+            numer: value,
+            denom: 1,
+        }
+    }
+
+    #[inline]
+    pub fn numer(&self) -> i128 {
+        self.numer
+    }
+
+    #[inline]
+    pub fn denom(&self) -> i128 {
+        self.denom
+    }
+
+    #[inline]
+    pub fn is_integer(&self) -> bool {
+        self.denom == 1
+    }
+
+    /// Returns the value as an integer, if it is one.
+    pub fn to_integer(self) -> Option<i128> {
+        self.is_integer().then_some(self.numer)
+    }
+
+    /// Rounds towards negative infinity.
+    pub fn floor(&self) -> i128 {
+        self.numer.div_euclid(self.denom)
+    }
+
+    pub fn recip(&self) -> Self {
+        Self::new(self.denom, self.numer)
+    }
+}
+
+impl From<i128> for Ratio {
+    fn from(value: i128) -> Self {
+        Self::from_integer(value)
+    }
+}
+
+impl From<i64> for Ratio {
+    fn from(value: i64) -> Self {
+        Self::from_integer(value.into())
+    }
+}
+
+impl From<i32> for Ratio {
+    fn from(value: i32) -> Self {
+        Self::from_integer(value.into())
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
+
+impl Debug for Ratio {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Both denominators are positive, so cross-multiplying keeps the order
+        if let (Some(left), Some(right)) = (
+            self.numer.checked_mul(other.denom),
+            other.numer.checked_mul(self.denom),
+        ) {
+            return left.cmp(&right);
+        }
+        // Otherwise compare the integer parts, and then the fractional parts,
+        // which are in the opposite order of their reciprocals
+        let (mut a, mut b, mut c, mut d) = (self.numer, self.denom, other.numer, other.denom);
+        let mut reversed = false;
+        let order = loop {
+            let (a_int, c_int) = (a.div_euclid(b), c.div_euclid(d));
+            if a_int != c_int {
+                break a_int.cmp(&c_int);
+            }
+            let (a_frac, c_frac) = (a.rem_euclid(b), c.rem_euclid(d));
+            if a_frac == 0 || c_frac == 0 {
+                break a_frac.cmp(&c_frac);
+            }
+            (a, b, c, d) = (b, a_frac, d, c_frac);
+            reversed = !reversed;
+        };
+        if reversed {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+impl Add for Ratio {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let divisor = gcd(self.denom, other.denom);
+        Self::new(
+            self.numer * (other.denom / divisor) + other.numer * (self.denom / divisor),
+            self.denom / divisor * other.denom,
+        )
+    }
+}
+
+impl Sub for Ratio {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        // Cancel common factors first, to keep the intermediate products small
+        let left = gcd(self.numer, other.denom);
+        let right = gcd(other.numer, self.denom);
+        Self::new(
+            (self.numer / left) * (other.numer / right),
+            (self.denom / right) * (other.denom / left),
+        )
+    }
+}
+
+impl Div for Ratio {
+    type Output = Self;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Self) -> Self {
+        self * other.recip()
+    }
+}
+
+impl Rem for Ratio {
+    type Output = Self;
+
+    /// The remainder of truncating division, like for integers.
+    fn rem(self, other: Self) -> Self {
+        let quotient = self / other;
+        self - other * Self::from_integer(quotient.numer / quotient.denom)
+    }
+}
+
+impl Neg for Ratio {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            numer: -self.numer,
+            denom: self.denom,
+        }
+    }
+}
+
+impl AddAssign for Ratio {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Ratio {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign for Ratio {
+    fn mul_assign(&mut self, other: Self) {
+        *self = *self * other;
+    }
+}
+
+impl DivAssign for Ratio {
+    fn div_assign(&mut self, other: Self) {
+        *self = *self / other;
+    }
+}
+
+impl RemAssign for Ratio {
+    fn rem_assign(&mut self, other: Self) {
+        *self = *self % other;
+    }
+}
+
+impl Sum for Ratio {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl Zero for Ratio {
+    fn zero() -> Self {
+        Self::from_integer(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.numer == 0
+    }
+}
+
+impl One for Ratio {
+    fn one() -> Self {
+        Self::from_integer(1)
+    }
+}
+
+impl num_traits::Num for Ratio {
+    type FromStrRadixErr = ParseError;
+
+    /// Parses either an integer or a fraction like `-3/4`.
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        let parse = |s: &str| {
+            i128::from_str_radix(s, radix).map_err(|_| ParseError::expected(s, "integer"))
+        };
+        let Some((numer, denom)) = s.split_once('/') else {
+            return parse(s).map(Self::from_integer);
+        };
+        match parse(denom)? {
+            0 => Err(ParseError::expected(denom, "non-zero denominator")),
+            value => Ok(Self::new(parse(numer)?, value)),
+        }
+    }
+}
+
+impl AsPrimitive<f64> for Ratio {
+    fn as_(self) -> f64 {
+        self.numer as f64 / self.denom as f64
+    }
+}
+
+impl Num for Ratio {
+    fn abs(self) -> Self {
+        Self {
+            numer: self.numer.abs(),
+            denom: self.denom,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Vector2D;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Ratio::new(6, -4), Ratio::new(-3, 2));
+        assert_eq!(Ratio::new(0, -5), Ratio::zero());
+        assert_eq!(Ratio::new(6, 3).to_integer(), Some(2));
+        assert_eq!(Ratio::new(-7, 2).floor(), -4);
+        assert_eq!(Ratio::new(6, -4).to_string(), "-3/2");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let half = Ratio::new(1, 2);
+        let third = Ratio::new(1, 3);
+        assert_eq!(half + third, Ratio::new(5, 6));
+        assert_eq!(half - third, Ratio::new(1, 6));
+        assert_eq!(half * third, Ratio::new(1, 6));
+        assert_eq!(half / third, Ratio::new(3, 2));
+        assert_eq!(Ratio::new(7, 2) % Ratio::from(2), Ratio::new(3, 2));
+        assert_eq!(-half, Ratio::new(-1, 2));
+        assert_eq!(
+            [half, third, third].into_iter().sum::<Ratio>(),
+            Ratio::new(7, 6)
+        );
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(Ratio::new(1, 3) < Ratio::new(1, 2));
+        assert!(Ratio::new(-1, 2) < Ratio::new(-1, 3));
+        assert_eq!(Num::abs(Ratio::new(-1, 2)), Ratio::new(1, 2));
+        // Cross-multiplying these would overflow
+        let n = i128::MAX;
+        assert!(Ratio::new(n, n - 1) < Ratio::new(n - 1, n - 2));
+        assert!(Ratio::new(-n, n - 1) > Ratio::new(-(n - 1), n - 2));
+        assert!(Ratio::new(n, 3) > Ratio::new(n - 1, 3));
+        assert!(Ratio::new(n - 2, n - 1) < Ratio::from_integer(1));
+        assert_eq!(
+            Ratio::new(n - 1, n).cmp(&Ratio::new(n - 1, n)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        use num_traits::Num;
+        assert_eq!(
+            Ratio::from_str_radix("-3/6", 10).unwrap(),
+            Ratio::new(-1, 2)
+        );
+        assert_eq!(Ratio::from_str_radix("42", 10).unwrap(), Ratio::from(42));
+        assert!(Ratio::from_str_radix("1/0", 10).is_err());
+    }
+
+    #[test]
+    fn test_vector() {
+        let v = Vector2D::new(Ratio::new(1, 2), Ratio::new(1, 3)) * Ratio::from(6);
+        assert_eq!(v, Vector2D::new(Ratio::from(3), Ratio::from(2)));
+        assert_eq!(v.dot_product(v), Ratio::from(13));
+    }
+}